@@ -0,0 +1,158 @@
+//! Hands the most recent input and output samples from the audio thread to the editor's analyzers
+//! without locking.
+
+use atomic_float::AtomicF32;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Enough history for the largest analysis window and the oscilloscope's longest time base, with
+/// room to spare so the editor can read a window before the audio thread gets around to
+/// overwriting it.
+pub const RING_CAPACITY: usize = 1 << 16;
+/// The taps are decimated down to at most this rate, so the rings hold the same length of history
+/// at higher sample rates.
+const MAX_TAP_RATE: f32 = 48000.0;
+
+/// A single producer ring buffer that only ever keeps the latest samples. The audio thread never
+/// waits for the reader: if the editor falls behind it simply sees newer samples.
+pub(crate) struct SampleRing {
+    samples: Vec<AtomicF32>,
+    /// The total number of samples written so far. The next sample goes to this position modulo
+    /// [`RING_CAPACITY`].
+    written: AtomicUsize,
+}
+
+impl Default for SampleRing {
+    fn default() -> Self {
+        Self {
+            samples: (0..RING_CAPACITY).map(|_| AtomicF32::new(0.0)).collect(),
+            written: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl SampleRing {
+    /// Append a sample. Only the audio thread may call this.
+    pub fn push(&self, sample: f32) {
+        let written = self.written.load(Ordering::Relaxed);
+        self.samples[written % RING_CAPACITY].store(sample, Ordering::Relaxed);
+        self.written
+            .store(written.wrapping_add(1), Ordering::Release);
+    }
+
+    /// The total number of samples written so far, which changes whenever there's new data.
+    pub fn written(&self) -> usize {
+        self.written.load(Ordering::Acquire)
+    }
+
+    /// Copy the latest `output.len()` samples into `output`, oldest first.
+    pub fn read_latest(&self, output: &mut [f32]) {
+        self.read_until(self.written(), output);
+    }
+
+    /// Copy the `output.len()` samples leading up to the total sample count `end` into `output`,
+    /// oldest first. `end` should lie within the last [`RING_CAPACITY`] samples written.
+    pub fn read_until(&self, end: usize, output: &mut [f32]) {
+        let start = end.wrapping_sub(output.len());
+        for (idx, sample) in output.iter_mut().enumerate() {
+            *sample = self.samples[start.wrapping_add(idx) % RING_CAPACITY].load(Ordering::Relaxed);
+        }
+    }
+}
+
+/// The signals the analyzers look at, all summed to mono.
+pub(crate) struct AnalyzerTaps {
+    /// The signal going into the cascades.
+    pub input: SampleRing,
+    /// The cascades' output before it's mixed with the dry signal.
+    pub wet: SampleRing,
+    /// The plugin's output.
+    pub output: SampleRing,
+    /// The rate the rings are written at, after decimation.
+    sample_rate: AtomicF32,
+}
+
+impl Default for AnalyzerTaps {
+    fn default() -> Self {
+        Self {
+            input: SampleRing::default(),
+            wet: SampleRing::default(),
+            output: SampleRing::default(),
+            sample_rate: AtomicF32::new(44100.0),
+        }
+    }
+}
+
+impl AnalyzerTaps {
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate.load(Ordering::Relaxed)
+    }
+
+    fn push(&self, input: f32, wet: f32, output: f32) {
+        self.input.push(input);
+        self.output.push(output);
+        // Written last, so a reader that sees a new wet sample also sees the other two
+        self.wet.push(wet);
+    }
+}
+
+/// The audio thread's side of the taps. Every `factor` frames are averaged into a single sample,
+/// which also keeps most of the content above the decimated rate's Nyquist frequency from
+/// aliasing.
+pub struct TapDecimator {
+    factor: usize,
+    count: usize,
+    /// The input, the wet signal and the output summed over the current `factor` frames.
+    sums: [f32; 3],
+}
+
+impl Default for TapDecimator {
+    fn default() -> Self {
+        Self {
+            factor: 1,
+            count: 0,
+            sums: [0.0; 3],
+        }
+    }
+}
+
+impl TapDecimator {
+    /// Set up the decimation for `sample_rate`, and tell the editor about the resulting rate.
+    pub(crate) fn new(sample_rate: f32, taps: &AnalyzerTaps) -> Self {
+        let factor = (sample_rate / MAX_TAP_RATE).ceil().max(1.0) as usize;
+        taps.sample_rate
+            .store(sample_rate / factor as f32, Ordering::Relaxed);
+
+        Self {
+            factor,
+            ..Self::default()
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.count = 0;
+        self.sums = [0.0; 3];
+    }
+
+    /// Add one frame of the input, the wet signal and the output, mixed down to mono. Writes to
+    /// the taps once every `factor` frames.
+    pub(crate) fn push(&mut self, taps: &AnalyzerTaps, input: &[f32], wet: &[f32], output: &[f32]) {
+        for (sum, frame) in self.sums.iter_mut().zip([input, wet, output]) {
+            *sum += mix_down(frame);
+        }
+
+        self.count += 1;
+        if self.count == self.factor {
+            let [input, wet, output] = self.sums.map(|sum| sum / self.factor as f32);
+            taps.push(input, wet, output);
+            self.reset();
+        }
+    }
+}
+
+fn mix_down(frame: &[f32]) -> f32 {
+    if frame.is_empty() {
+        0.0
+    } else {
+        frame.iter().sum::<f32>() / frame.len() as f32
+    }
+}
@@ -0,0 +1,72 @@
+//! A plain biquad filter, used for the crossovers, the oversampling filters and the loudness meter.
+
+/// A transposed direct form II biquad.
+#[derive(Clone, Copy, Default)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    /// Set the coefficients for a lowpass filter, keeping the filter's state.
+    pub fn set_lowpass(&mut self, sample_rate: f32, frequency: f32, q: f32) {
+        self.set_coefficients(sample_rate, frequency, q, false);
+    }
+
+    /// Set the coefficients for a highpass filter, keeping the filter's state.
+    pub fn set_highpass(&mut self, sample_rate: f32, frequency: f32, q: f32) {
+        self.set_coefficients(sample_rate, frequency, q, true);
+    }
+
+    fn set_coefficients(&mut self, sample_rate: f32, frequency: f32, q: f32, highpass: bool) {
+        let omega = std::f32::consts::TAU * frequency / sample_rate;
+        let (sin, cos) = omega.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let a0 = 1.0 + alpha;
+
+        let (b0, b1) = if highpass {
+            ((1.0 + cos) / 2.0, -(1.0 + cos))
+        } else {
+            ((1.0 - cos) / 2.0, 1.0 - cos)
+        };
+        self.b0 = b0 / a0;
+        self.b1 = b1 / a0;
+        self.b2 = b0 / a0;
+        self.a1 = -2.0 * cos / a0;
+        self.a2 = (1.0 - alpha) / a0;
+    }
+
+    /// Set the coefficients for a high shelf that boosts by `gain_db` above `frequency`, keeping
+    /// the filter's state.
+    pub fn set_high_shelf(&mut self, sample_rate: f32, frequency: f32, q: f32, gain_db: f32) {
+        let k = (std::f32::consts::PI * frequency / sample_rate).tan();
+        let high_gain = 10.0f32.powf(gain_db / 20.0);
+        let mid_gain = high_gain.sqrt();
+        let a0 = 1.0 + k / q + k * k;
+
+        self.b0 = (high_gain + mid_gain * k / q + k * k) / a0;
+        self.b1 = 2.0 * (k * k - high_gain) / a0;
+        self.b2 = (high_gain - mid_gain * k / q + k * k) / a0;
+        self.a1 = 2.0 * (k * k - 1.0) / a0;
+        self.a2 = (1.0 - k / q + k * k) / a0;
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let output = self.b0 * sample + self.z1;
+        self.z1 = self.b1 * sample - self.a1 * output + self.z2;
+        self.z2 = self.b2 * sample - self.a2 * output;
+
+        output
+    }
+}
@@ -0,0 +1,131 @@
+//! A disperser cascade that can change its number of stages without clicking.
+
+use i_am_dsp::{Effect, ProcessContext as DspContext, prelude::Disperser};
+
+use crate::MAX_AMOUNT;
+
+/// How long it takes to fade over to a cascade with a different number of stages.
+const CROSSFADE_MS: f32 = 40.0;
+
+/// Two [`Disperser`] cascades. Changing the stage count of a running cascade adds or drops stages
+/// mid-stream, so instead the idle cascade is set up with the new count and the output fades over
+/// to it. Only the active cascade runs outside of a crossfade.
+pub struct CrossfadingDisperser<const N: usize> {
+    dispersers: [Disperser<N>; 2],
+    biquad_counts: [usize; 2],
+    /// The index of the cascade that is currently audible.
+    active: usize,
+    /// The progress of the fade from the active cascade to the other one, if one is running.
+    fade: Option<f32>,
+    fade_step: f32,
+    /// Set by [`reset()`][Self::reset()]. A cleared cascade has nothing to fade from, so the next
+    /// change to the stage count is made in place.
+    snap: bool,
+
+    /// The frequency and spread the coefficients were last computed for.
+    filter_parameters: (f32, f32),
+}
+
+impl<const N: usize> CrossfadingDisperser<N> {
+    pub fn new(sample_rate: f32) -> Self {
+        let dispersers = std::array::from_fn(|_| {
+            // Growing the cascade to its largest size once means adding stages later on only
+            // reuses memory that's already there, instead of allocating on the audio thread
+            let mut disperser = Disperser::<N>::new(sample_rate as usize);
+            disperser.set_biquad_count(MAX_AMOUNT as usize);
+            disperser.set_biquad_count(0);
+            disperser
+        });
+
+        Self {
+            dispersers,
+            biquad_counts: [0; 2],
+            active: 0,
+            fade: None,
+            fade_step: (sample_rate * CROSSFADE_MS / 1000.0).recip(),
+            snap: false,
+
+            filter_parameters: (0.0, 0.0),
+        }
+    }
+
+    /// Clear both cascades and stop any running fade.
+    pub fn reset(&mut self) {
+        self.fade = None;
+        for idx in 0..2 {
+            // Like in `set_biquad_count()`, dropping all stages clears what they still hold
+            let count = self.biquad_counts[idx];
+            self.dispersers[idx].set_biquad_count(0);
+            self.dispersers[idx].set_biquad_count(count);
+            self.apply_filter_parameters(idx);
+        }
+        self.snap = true;
+    }
+
+    /// Recompute both cascades' coefficients, if the parameters changed since the last call.
+    pub fn set_filter_parameters(&mut self, freq: f32, spread: f32) {
+        if self.filter_parameters != (freq, spread) {
+            for disperser in &mut self.dispersers {
+                disperser.set_filter_parameters(freq, spread);
+            }
+            self.filter_parameters = (freq, spread);
+        }
+    }
+
+    /// Change the number of allpass stages. With `crossfade` the output fades over to a fresh
+    /// cascade, otherwise the active cascade is changed in place. A change that comes in while a
+    /// fade is still running is picked up on the next call after it finishes.
+    pub fn set_biquad_count(&mut self, count: usize, crossfade: bool) {
+        let snap = std::mem::take(&mut self.snap);
+        if self.fade.is_some() || self.biquad_counts[self.active] == count {
+            return;
+        }
+
+        if crossfade && !snap {
+            let next = 1 - self.active;
+
+            // Dropping all stages first means the new cascade starts from silence instead of
+            // whatever it still held from the last time it was used
+            self.dispersers[next].set_biquad_count(0);
+            self.dispersers[next].set_biquad_count(count);
+            self.apply_filter_parameters(next);
+            self.biquad_counts[next] = count;
+            self.fade = Some(0.0);
+        } else {
+            self.dispersers[self.active].set_biquad_count(count);
+            self.apply_filter_parameters(self.active);
+            self.biquad_counts[self.active] = count;
+        }
+    }
+
+    /// Make sure newly added stages use the current coefficients.
+    fn apply_filter_parameters(&mut self, idx: usize) {
+        let (freq, spread) = self.filter_parameters;
+        self.dispersers[idx].set_filter_parameters(freq, spread);
+    }
+
+    pub fn process(&mut self, frame: &mut [f32; N], dsp_ctx: &mut Box<dyn DspContext>) {
+        let other_inputs: &[&[f32; N]] = &[];
+
+        match self.fade {
+            None => self.dispersers[self.active].process(frame, other_inputs, dsp_ctx),
+            Some(progress) => {
+                let mut next_frame = *frame;
+                self.dispersers[self.active].process(frame, other_inputs, dsp_ctx);
+                self.dispersers[1 - self.active].process(&mut next_frame, other_inputs, dsp_ctx);
+
+                for (sample, next_sample) in frame.iter_mut().zip(next_frame) {
+                    *sample += (next_sample - *sample) * progress;
+                }
+
+                let progress = progress + self.fade_step;
+                if progress >= 1.0 {
+                    self.active = 1 - self.active;
+                    self.fade = None;
+                } else {
+                    self.fade = Some(progress);
+                }
+            }
+        }
+    }
+}
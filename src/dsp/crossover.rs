@@ -0,0 +1,59 @@
+//! Fourth order Linkwitz-Riley crossovers. The two outputs of a split sum back to an allpass, so
+//! bands that are split apart and added back together keep a flat magnitude response.
+
+use crate::dsp::biquad::Biquad;
+
+/// The Butterworth Q. Two of these sections in series make one side of a Linkwitz-Riley split.
+const BUTTERWORTH_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+#[derive(Clone, Copy, Default)]
+pub struct LinkwitzRiley {
+    lowpass: [Biquad; 2],
+    highpass: [Biquad; 2],
+    /// The frequency the coefficients were last computed for.
+    frequency: f32,
+}
+
+impl LinkwitzRiley {
+    /// Recompute the coefficients, if the frequency changed since the last call.
+    pub fn set_frequency(&mut self, sample_rate: f32, frequency: f32) {
+        if self.frequency == frequency {
+            return;
+        }
+
+        for biquad in &mut self.lowpass {
+            biquad.set_lowpass(sample_rate, frequency, BUTTERWORTH_Q);
+        }
+        for biquad in &mut self.highpass {
+            biquad.set_highpass(sample_rate, frequency, BUTTERWORTH_Q);
+        }
+        self.frequency = frequency;
+    }
+
+    pub fn reset(&mut self) {
+        for biquad in self.lowpass.iter_mut().chain(&mut self.highpass) {
+            biquad.reset();
+        }
+    }
+
+    /// Split `sample` into its low and high band.
+    pub fn split(&mut self, sample: f32) -> (f32, f32) {
+        let low = self
+            .lowpass
+            .iter_mut()
+            .fold(sample, |sample, biquad| biquad.process(sample));
+        let high = self
+            .highpass
+            .iter_mut()
+            .fold(sample, |sample, biquad| biquad.process(sample));
+
+        (low, high)
+    }
+
+    /// Apply the same phase shift a split adds to the summed bands, without splitting anything.
+    /// Bands that didn't go through this split need this to stay in phase with the ones that did.
+    pub fn allpass(&mut self, sample: f32) -> f32 {
+        let (low, high) = self.split(sample);
+        low + high
+    }
+}
@@ -0,0 +1,144 @@
+//! An envelope follower that lets the input's level drive the disperser.
+
+use nih_plug::prelude::*;
+
+/// How far above the threshold the envelope needs to go to reach its full depth, in decibels.
+const ENVELOPE_RANGE_DB: f32 = 24.0;
+/// How far a fully deep envelope moves the frequency or spread, in octaves.
+pub const ENVELOPE_RANGE_OCTAVES: f32 = 4.0;
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModulationTarget {
+    #[id = "frequency"]
+    Frequency,
+    #[id = "spread"]
+    Spread,
+    #[id = "amount"]
+    Amount,
+}
+
+/// The signal the envelope follower listens to.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvelopeSource {
+    /// The plugin's own input, after the input gain.
+    #[id = "input"]
+    Input,
+    /// The auxiliary sidechain input, e.g. a kick from another track.
+    #[id = "sidechain"]
+    Sidechain,
+}
+
+#[derive(Params)]
+pub struct EnvelopeParams {
+    #[id = "env_source"]
+    pub source: EnumParam<EnvelopeSource>,
+
+    #[id = "env_attack"]
+    pub attack: FloatParam,
+
+    #[id = "env_release"]
+    pub release: FloatParam,
+
+    #[id = "env_threshold"]
+    pub threshold: FloatParam,
+
+    /// Bipolar, negative depths move the target the other way on loud hits.
+    #[id = "env_depth"]
+    pub depth: FloatParam,
+
+    #[id = "env_target"]
+    pub target: EnumParam<ModulationTarget>,
+}
+
+impl Default for EnvelopeParams {
+    fn default() -> Self {
+        Self {
+            source: EnumParam::new("Envelope Source", EnvelopeSource::Input),
+
+            attack: FloatParam::new(
+                "Envelope Attack",
+                5.0,
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 200.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            release: FloatParam::new(
+                "Envelope Release",
+                150.0,
+                FloatRange::Skewed {
+                    min: 5.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            threshold: FloatParam::new(
+                "Envelope Threshold",
+                -30.0,
+                FloatRange::Linear {
+                    min: -60.0,
+                    max: 0.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            depth: FloatParam::new(
+                "Envelope Depth",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            target: EnumParam::new("Envelope Target", ModulationTarget::Amount),
+        }
+    }
+}
+
+/// A peak envelope follower with separate attack and release times.
+#[derive(Default)]
+pub struct EnvelopeFollower {
+    envelope: f32,
+    attack_weight: f32,
+    release_weight: f32,
+}
+
+impl EnvelopeFollower {
+    pub fn set_times(&mut self, sample_rate: f32, attack_ms: f32, release_ms: f32) {
+        self.attack_weight = (-1.0 / (sample_rate * attack_ms / 1000.0)).exp();
+        self.release_weight = (-1.0 / (sample_rate * release_ms / 1000.0)).exp();
+    }
+
+    pub fn reset(&mut self) {
+        self.envelope = 0.0;
+    }
+
+    /// Feed the follower the next absolute input level.
+    pub fn process(&mut self, level: f32) {
+        let weight = if level > self.envelope {
+            self.attack_weight
+        } else {
+            self.release_weight
+        };
+        self.envelope = self.envelope * weight + level * (1.0 - weight);
+    }
+
+    /// How far the envelope is above `threshold_db`, scaled to `[0, 1]`.
+    pub fn amount_above(&self, threshold_db: f32) -> f32 {
+        let envelope_db = util::gain_to_db(self.envelope);
+        ((envelope_db - threshold_db) / ENVELOPE_RANGE_DB).clamp(0.0, 1.0)
+    }
+}
@@ -0,0 +1,104 @@
+//! A damped feedback path around the allpass cascades for resonant, metallic tails.
+
+use nih_plug::prelude::*;
+
+use crate::MAX_CHANNELS;
+
+/// The loop can never reach unity gain, on top of the soft clipping inside of it.
+const MAX_FEEDBACK: f32 = 0.95;
+/// The level the tail needs to decay to before the plugin stops reporting it to the host.
+pub const TAIL_SILENCE_DB: f32 = -90.0;
+
+#[derive(Params)]
+pub struct FeedbackParams {
+    /// Negative values flip the polarity of the fed back signal.
+    #[id = "feedback"]
+    pub gain: FloatParam,
+
+    /// The cutoff of the lowpass filter inside the loop.
+    #[id = "feedback_damping"]
+    pub damping: FloatParam,
+}
+
+impl Default for FeedbackParams {
+    fn default() -> Self {
+        Self {
+            gain: FloatParam::new(
+                "Feedback",
+                0.0,
+                FloatRange::Linear {
+                    min: -MAX_FEEDBACK,
+                    max: MAX_FEEDBACK,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            damping: FloatParam::new(
+                "Feedback Damping",
+                8000.0,
+                FloatRange::Skewed {
+                    min: 200.0,
+                    max: 20000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+        }
+    }
+}
+
+/// The per-channel state of the feedback loop. The cascade's output is lowpassed and added back
+/// to its input one sample later.
+#[derive(Default)]
+pub struct FeedbackLoop {
+    /// The damped cascade output from the previous sample.
+    state: [f32; MAX_CHANNELS],
+    damping_weight: f32,
+}
+
+impl FeedbackLoop {
+    pub fn reset(&mut self) {
+        self.state = [0.0; MAX_CHANNELS];
+    }
+
+    pub fn set_damping(&mut self, sample_rate: f32, cutoff: f32) {
+        self.damping_weight = (-std::f32::consts::TAU * cutoff / sample_rate).exp();
+    }
+
+    /// The cascade's input for `channel`, with the fed back signal mixed in. The fed back part is
+    /// soft clipped, which keeps the loop bounded no matter what goes into it.
+    pub fn input(&self, channel: usize, sample: f32, gain: f32) -> f32 {
+        if gain == 0.0 {
+            return sample;
+        }
+
+        sample + (self.state[channel] * gain.clamp(-MAX_FEEDBACK, MAX_FEEDBACK)).tanh()
+    }
+
+    /// Store the cascade's output for `channel` so it can be fed back on the next sample.
+    pub fn output(&mut self, channel: usize, sample: f32) {
+        self.state[channel] =
+            self.state[channel] * self.damping_weight + sample * (1.0 - self.damping_weight);
+    }
+
+    /// Estimate how many samples it takes for what's left in the loop to decay into silence.
+    /// `loop_samples` is the time one trip around the loop takes.
+    pub fn tail_samples(&self, gain: f32, loop_samples: f32) -> u32 {
+        let gain = gain.abs().min(MAX_FEEDBACK);
+        let level = self
+            .state
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        let silence = util::db_to_gain(TAIL_SILENCE_DB);
+        if gain == 0.0 || level <= silence {
+            return 0;
+        }
+
+        let round_trips = (silence / level).ln() / gain.ln();
+        (round_trips * loop_samples).ceil() as u32
+    }
+}
@@ -0,0 +1,132 @@
+//! MIDI keytracking, so the dispersion frequency can follow the notes being played.
+
+use nih_plug::prelude::*;
+
+/// The number of held notes that are remembered for falling back to when a note is released.
+const MAX_HELD_NOTES: usize = 16;
+
+#[derive(Params)]
+pub struct KeytrackParams {
+    /// When enabled the last held note replaces the frequency parameter.
+    #[id = "keytrack"]
+    pub enabled: BoolParam,
+
+    #[id = "keytrack_octave"]
+    pub octave: IntParam,
+
+    #[id = "keytrack_semitone"]
+    pub semitone: IntParam,
+
+    #[id = "keytrack_glide"]
+    pub glide: FloatParam,
+}
+
+impl Default for KeytrackParams {
+    fn default() -> Self {
+        Self {
+            enabled: BoolParam::new("Keytrack", false),
+
+            octave: IntParam::new("Keytrack Octave", 0, IntRange::Linear { min: -4, max: 4 })
+                .with_unit(" oct"),
+            semitone: IntParam::new(
+                "Keytrack Semitone",
+                0,
+                IntRange::Linear { min: -12, max: 12 },
+            )
+            .with_unit(" st"),
+
+            glide: FloatParam::new(
+                "Keytrack Glide",
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+        }
+    }
+}
+
+impl KeytrackParams {
+    /// The offset added to incoming notes, in semitones.
+    pub fn transpose(&self) -> f32 {
+        (self.octave.value() * 12 + self.semitone.value()) as f32
+    }
+}
+
+/// Tracks the held notes with last note priority and glides between them.
+pub struct Keytracker {
+    held_notes: [u8; MAX_HELD_NOTES],
+    num_held_notes: usize,
+    /// The note the glide is heading to. `None` until the first note comes in, after that the last
+    /// note is held even when all keys are released.
+    target_note: Option<f32>,
+    current_note: f32,
+}
+
+impl Default for Keytracker {
+    fn default() -> Self {
+        Self {
+            held_notes: [0; MAX_HELD_NOTES],
+            num_held_notes: 0,
+            target_note: None,
+            current_note: 0.0,
+        }
+    }
+}
+
+impl Keytracker {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn note_on(&mut self, note: u8) {
+        self.remove_held_note(note);
+        if self.num_held_notes == MAX_HELD_NOTES {
+            self.held_notes.copy_within(1.., 0);
+            self.num_held_notes -= 1;
+        }
+        self.held_notes[self.num_held_notes] = note;
+        self.num_held_notes += 1;
+
+        if self.target_note.is_none() {
+            self.current_note = note as f32;
+        }
+        self.target_note = Some(note as f32);
+    }
+
+    pub fn note_off(&mut self, note: u8) {
+        self.remove_held_note(note);
+        if let Some(&last_note) = self.held_notes[..self.num_held_notes].last() {
+            self.target_note = Some(last_note as f32);
+        }
+    }
+
+    /// Glide towards the target note and return the current (fractional) note, if any note has been
+    /// played yet. `glide_weight` is the one-pole weight for the time since the last call.
+    pub fn next_note(&mut self, glide_weight: f32) -> Option<f32> {
+        let target_note = self.target_note?;
+        self.current_note = target_note + (self.current_note - target_note) * glide_weight;
+
+        Some(self.current_note)
+    }
+
+    fn remove_held_note(&mut self, note: u8) {
+        if let Some(idx) = self.held_notes[..self.num_held_notes]
+            .iter()
+            .position(|&held_note| held_note == note)
+        {
+            self.held_notes
+                .copy_within(idx + 1..self.num_held_notes, idx);
+            self.num_held_notes -= 1;
+        }
+    }
+}
+
+/// Convert a (fractional) MIDI note number to a frequency in Hz.
+pub fn note_to_freq(note: f32) -> f32 {
+    440.0 * 2.0f32.powf((note - 69.0) / 12.0)
+}
@@ -0,0 +1,208 @@
+//! A simple LFO for sweeping the disperser's frequency or spread.
+
+use nih_plug::prelude::*;
+use std::f32::consts::TAU;
+
+/// How far a fully deep LFO moves its target up and down, in octaves.
+pub const LFO_RANGE_OCTAVES: f32 = 3.0;
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LfoShape {
+    #[id = "sine"]
+    Sine,
+    #[id = "triangle"]
+    Triangle,
+    #[id = "saw"]
+    Saw,
+    #[id = "sample_and_hold"]
+    #[name = "S&H"]
+    SampleAndHold,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LfoTarget {
+    #[id = "frequency"]
+    Frequency,
+    #[id = "spread"]
+    Spread,
+}
+
+/// The length of one LFO cycle when it's synced to the host's tempo.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LfoDivision {
+    #[id = "4_1"]
+    #[name = "4/1"]
+    FourBars,
+    #[id = "2_1"]
+    #[name = "2/1"]
+    TwoBars,
+    #[id = "1_1"]
+    #[name = "1/1"]
+    Bar,
+    #[id = "1_2"]
+    #[name = "1/2"]
+    Half,
+    #[id = "1_4"]
+    #[name = "1/4"]
+    Quarter,
+    #[id = "1_8"]
+    #[name = "1/8"]
+    Eighth,
+    #[id = "1_16"]
+    #[name = "1/16"]
+    Sixteenth,
+    #[id = "1_4t"]
+    #[name = "1/4T"]
+    QuarterTriplet,
+    #[id = "1_8t"]
+    #[name = "1/8T"]
+    EighthTriplet,
+    #[id = "1_4d"]
+    #[name = "1/4D"]
+    DottedQuarter,
+    #[id = "1_8d"]
+    #[name = "1/8D"]
+    DottedEighth,
+}
+
+impl LfoDivision {
+    /// The cycle length in quarter notes.
+    pub fn beats(self) -> f64 {
+        match self {
+            LfoDivision::FourBars => 16.0,
+            LfoDivision::TwoBars => 8.0,
+            LfoDivision::Bar => 4.0,
+            LfoDivision::Half => 2.0,
+            LfoDivision::Quarter => 1.0,
+            LfoDivision::Eighth => 0.5,
+            LfoDivision::Sixteenth => 0.25,
+            LfoDivision::QuarterTriplet => 2.0 / 3.0,
+            LfoDivision::EighthTriplet => 1.0 / 3.0,
+            LfoDivision::DottedQuarter => 1.5,
+            LfoDivision::DottedEighth => 0.75,
+        }
+    }
+}
+
+#[derive(Params)]
+pub struct LfoParams {
+    #[id = "lfo_shape"]
+    pub shape: EnumParam<LfoShape>,
+
+    /// The free running rate, used when the LFO is not synced to the host.
+    #[id = "lfo_rate"]
+    pub rate: FloatParam,
+
+    #[id = "lfo_sync"]
+    pub sync: BoolParam,
+
+    #[id = "lfo_division"]
+    pub division: EnumParam<LfoDivision>,
+
+    #[id = "lfo_depth"]
+    pub depth: FloatParam,
+
+    #[id = "lfo_target"]
+    pub target: EnumParam<LfoTarget>,
+}
+
+impl Default for LfoParams {
+    fn default() -> Self {
+        Self {
+            shape: EnumParam::new("LFO Shape", LfoShape::Sine),
+
+            rate: FloatParam::new(
+                "LFO Rate",
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.01,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            sync: BoolParam::new("LFO Sync", false),
+            division: EnumParam::new("LFO Division", LfoDivision::Quarter),
+
+            depth: FloatParam::new("LFO Depth", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            target: EnumParam::new("LFO Target", LfoTarget::Frequency),
+        }
+    }
+}
+
+impl LfoParams {
+    /// How far the LFO's phase moves per sample, in cycles. Uses the host's tempo when synced and
+    /// the host provides one.
+    pub fn phase_delta(&self, sample_rate: f32, tempo: Option<f64>) -> f32 {
+        match tempo {
+            Some(tempo) if self.sync.value() => {
+                (tempo / 60.0 / self.division.value().beats() / sample_rate as f64) as f32
+            }
+            _ => self.rate.value() / sample_rate,
+        }
+    }
+}
+
+pub struct Lfo {
+    /// The position within the current cycle, in `[0, 1)`.
+    phase: f32,
+    /// The current value of the sample and hold shape, picked at the start of every cycle.
+    held_value: f32,
+    rng_state: u32,
+}
+
+impl Default for Lfo {
+    fn default() -> Self {
+        Self {
+            phase: 0.0,
+            held_value: 0.0,
+            rng_state: 0x9E37_79B9,
+        }
+    }
+}
+
+impl Lfo {
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    /// Jump to a phase, used to lock the LFO to the host's song position.
+    pub fn set_phase(&mut self, phase: f32) {
+        self.phase = phase.rem_euclid(1.0);
+    }
+
+    /// Get the LFO's current bipolar value and advance it by `phase_delta` cycles.
+    pub fn next(&mut self, shape: LfoShape, phase_delta: f32) -> f32 {
+        let phase = self.phase;
+        let value = match shape {
+            LfoShape::Sine => (phase * TAU).sin(),
+            LfoShape::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            LfoShape::Saw => phase * 2.0 - 1.0,
+            LfoShape::SampleAndHold => self.held_value,
+        };
+
+        self.phase += phase_delta;
+        if self.phase >= 1.0 {
+            self.phase = self.phase.fract();
+            self.held_value = self.next_random();
+        }
+
+        value
+    }
+
+    /// A xorshift random number in `[-1, 1]`.
+    fn next_random(&mut self) -> f32 {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 17;
+        self.rng_state ^= self.rng_state << 5;
+
+        (self.rng_state as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+}
@@ -0,0 +1,301 @@
+//! Per-channel peak and RMS levels plus short-term and integrated loudness, measured on the audio
+//! thread and read by the editor.
+
+use atomic_float::AtomicF32;
+use nih_plug::prelude::*;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::dsp::biquad::Biquad;
+use crate::{MAX_CHANNELS, one_pole_weight};
+
+/// Peaks fall back at the rate of an IEC type I PPM, 20 dB in 1.7 seconds.
+const PEAK_RELEASE_DB_PER_SECOND: f32 = 20.0 / 1.7;
+/// The integration time of the RMS level.
+const RMS_WINDOW_MS: f64 = 300.0;
+
+/// Loudness is measured in blocks of this length. Four of them make up a gating block for the
+/// integrated loudness, thirty of them the short-term loudness.
+const LOUDNESS_BLOCK_MS: f32 = 100.0;
+const GATING_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+/// BS.1770 gating blocks quieter than this never count towards the integrated loudness.
+const ABSOLUTE_GATE_LUFS: f32 = -70.0;
+/// Gating blocks this far below the ungated loudness don't count either.
+const RELATIVE_GATE_LU: f32 = -10.0;
+/// The integrated loudness is based on a histogram of the gating blocks, so it can keep running
+/// forever without allocating. These bins cover -70 to +30 LUFS.
+const HISTOGRAM_STEP_LU: f32 = 0.1;
+const HISTOGRAM_BINS: usize = 1000;
+
+/// The BS.1770 weight of a surround channel.
+const SURROUND_WEIGHT: f32 = 1.41;
+
+/// The levels the editor shows. Everything is in dB or LUFS.
+pub(crate) struct MeterLevels {
+    channels: AtomicUsize,
+    peak: [AtomicF32; MAX_CHANNELS],
+    rms: [AtomicF32; MAX_CHANNELS],
+    short_term: AtomicF32,
+    integrated: AtomicF32,
+    reset_requested: AtomicBool,
+}
+
+impl Default for MeterLevels {
+    fn default() -> Self {
+        Self {
+            channels: AtomicUsize::new(2),
+            peak: std::array::from_fn(|_| AtomicF32::new(util::MINUS_INFINITY_DB)),
+            rms: std::array::from_fn(|_| AtomicF32::new(util::MINUS_INFINITY_DB)),
+            short_term: AtomicF32::new(util::MINUS_INFINITY_DB),
+            integrated: AtomicF32::new(util::MINUS_INFINITY_DB),
+            reset_requested: AtomicBool::new(false),
+        }
+    }
+}
+
+impl MeterLevels {
+    /// The number of channels being measured, at most [`MAX_CHANNELS`].
+    pub fn channels(&self) -> usize {
+        self.channels.load(Ordering::Relaxed)
+    }
+
+    pub fn peak_db(&self, channel: usize) -> f32 {
+        self.peak[channel].load(Ordering::Relaxed)
+    }
+
+    pub fn rms_db(&self, channel: usize) -> f32 {
+        self.rms[channel].load(Ordering::Relaxed)
+    }
+
+    pub fn short_term_lufs(&self) -> f32 {
+        self.short_term.load(Ordering::Relaxed)
+    }
+
+    pub fn integrated_lufs(&self) -> f32 {
+        self.integrated.load(Ordering::Relaxed)
+    }
+
+    /// Start measuring the integrated loudness from scratch.
+    pub fn request_reset(&self) {
+        self.reset_requested.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The audio thread's side of the metering.
+pub struct Meter {
+    channels: usize,
+
+    peak: [f32; MAX_CHANNELS],
+    peak_release_weight: f32,
+    mean_square: [f32; MAX_CHANNELS],
+    rms_weight: f32,
+
+    /// The K-weighting filters, a high shelf followed by a highpass.
+    k_weighting: [[Biquad; 2]; MAX_CHANNELS],
+    /// How much each channel counts towards the loudness.
+    loudness_weights: [f32; MAX_CHANNELS],
+    block_len: usize,
+    block_pos: usize,
+    block_square_sums: [f32; MAX_CHANNELS],
+    /// The mean square of the last [`SHORT_TERM_BLOCKS`] loudness blocks, summed over the
+    /// channels with their loudness weights.
+    blocks: [f32; SHORT_TERM_BLOCKS],
+    block_idx: usize,
+    num_blocks: usize,
+
+    /// The number of gating blocks and their summed mean squares, binned by loudness.
+    histogram_counts: Vec<u32>,
+    histogram_energies: Vec<f64>,
+    histogram_changed: bool,
+    integrated: f32,
+}
+
+impl Meter {
+    pub fn new(sample_rate: f32, channels: usize) -> Self {
+        let mut k_weighting = [[Biquad::default(); 2]; MAX_CHANNELS];
+        for [shelf, highpass] in &mut k_weighting {
+            shelf.set_high_shelf(sample_rate, 1681.97, 0.707_175, 3.999_84);
+            highpass.set_highpass(sample_rate, 38.135_47, 0.500_327);
+        }
+
+        let channels = channels.min(MAX_CHANNELS);
+
+        Self {
+            channels,
+
+            peak: [0.0; MAX_CHANNELS],
+            peak_release_weight: util::db_to_gain(-PEAK_RELEASE_DB_PER_SECOND / sample_rate),
+            mean_square: [0.0; MAX_CHANNELS],
+            rms_weight: one_pole_weight(sample_rate, RMS_WINDOW_MS),
+
+            k_weighting,
+            loudness_weights: std::array::from_fn(|channel| loudness_weight(channel, channels)),
+            block_len: (sample_rate * LOUDNESS_BLOCK_MS / 1000.0).round() as usize,
+            block_pos: 0,
+            block_square_sums: [0.0; MAX_CHANNELS],
+            blocks: [0.0; SHORT_TERM_BLOCKS],
+            block_idx: 0,
+            num_blocks: 0,
+
+            histogram_counts: vec![0; HISTOGRAM_BINS],
+            histogram_energies: vec![0.0; HISTOGRAM_BINS],
+            histogram_changed: false,
+            integrated: util::MINUS_INFINITY_DB,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.peak = [0.0; MAX_CHANNELS];
+        self.mean_square = [0.0; MAX_CHANNELS];
+        for biquad in self.k_weighting.iter_mut().flatten() {
+            biquad.reset();
+        }
+        self.block_pos = 0;
+        self.block_square_sums = [0.0; MAX_CHANNELS];
+        self.num_blocks = 0;
+        self.reset_integrated();
+    }
+
+    fn reset_integrated(&mut self) {
+        self.histogram_counts.fill(0);
+        self.histogram_energies.fill(0.0);
+        self.histogram_changed = false;
+        self.integrated = util::MINUS_INFINITY_DB;
+    }
+
+    /// Measure one sample of every channel.
+    pub fn process(&mut self, frame: &[f32]) {
+        for (channel, &sample) in frame.iter().take(self.channels).enumerate() {
+            self.peak[channel] = sample
+                .abs()
+                .max(self.peak[channel] * self.peak_release_weight);
+            self.mean_square[channel] = self.mean_square[channel] * self.rms_weight
+                + sample * sample * (1.0 - self.rms_weight);
+
+            let weighted = self.k_weighting[channel]
+                .iter_mut()
+                .fold(sample, |sample, biquad| biquad.process(sample));
+            self.block_square_sums[channel] += weighted * weighted;
+        }
+
+        self.block_pos += 1;
+        if self.block_pos >= self.block_len {
+            self.finish_block();
+        }
+    }
+
+    fn finish_block(&mut self) {
+        let square_sum = self
+            .block_square_sums
+            .iter()
+            .zip(&self.loudness_weights)
+            .map(|(square_sum, weight)| square_sum * weight)
+            .sum::<f32>();
+        self.blocks[self.block_idx] = square_sum / self.block_len as f32;
+        self.block_idx = (self.block_idx + 1) % SHORT_TERM_BLOCKS;
+        self.num_blocks = (self.num_blocks + 1).min(SHORT_TERM_BLOCKS);
+        self.block_pos = 0;
+        self.block_square_sums = [0.0; MAX_CHANNELS];
+
+        // The gating blocks overlap by 75%, so every loudness block completes one
+        if self.num_blocks >= GATING_BLOCKS {
+            let energy = self.last_blocks_energy(GATING_BLOCKS);
+            let loudness = energy_to_lufs(energy);
+            if loudness >= ABSOLUTE_GATE_LUFS {
+                let bin = histogram_bin(loudness);
+                self.histogram_counts[bin] += 1;
+                self.histogram_energies[bin] += energy as f64;
+                self.histogram_changed = true;
+            }
+        }
+    }
+
+    /// The mean of the last `count` blocks' mean squares.
+    fn last_blocks_energy(&self, count: usize) -> f32 {
+        (1..=count)
+            .map(|age| self.blocks[(self.block_idx + SHORT_TERM_BLOCKS - age) % SHORT_TERM_BLOCKS])
+            .sum::<f32>()
+            / count as f32
+    }
+
+    fn update_integrated(&mut self) {
+        if !self.histogram_changed {
+            return;
+        }
+        self.histogram_changed = false;
+
+        let (count, energy) = self.gated_sums(0);
+        if count == 0 {
+            self.integrated = util::MINUS_INFINITY_DB;
+            return;
+        }
+
+        let relative_gate = energy_to_lufs((energy / count as f64) as f32) + RELATIVE_GATE_LU;
+        let (count, energy) = self.gated_sums(histogram_bin(relative_gate));
+        self.integrated = if count > 0 {
+            energy_to_lufs((energy / count as f64) as f32)
+        } else {
+            util::MINUS_INFINITY_DB
+        };
+    }
+
+    /// The number of gating blocks and their summed mean squares, from `first_bin` upwards.
+    fn gated_sums(&self, first_bin: usize) -> (u32, f64) {
+        self.histogram_counts[first_bin..]
+            .iter()
+            .zip(&self.histogram_energies[first_bin..])
+            .fold((0, 0.0), |(count, energy), (bin_count, bin_energy)| {
+                (count + bin_count, energy + bin_energy)
+            })
+    }
+
+    /// Store the current levels for the editor. Also picks up the editor's reset requests, so
+    /// this should be called once per block.
+    pub fn publish(&mut self, levels: &MeterLevels) {
+        if levels.reset_requested.swap(false, Ordering::Relaxed) {
+            self.reset_integrated();
+        }
+        self.update_integrated();
+
+        levels.channels.store(self.channels, Ordering::Relaxed);
+        for channel in 0..self.channels {
+            levels.peak[channel].store(util::gain_to_db(self.peak[channel]), Ordering::Relaxed);
+            levels.rms[channel].store(
+                util::gain_to_db(self.mean_square[channel].sqrt()),
+                Ordering::Relaxed,
+            );
+        }
+
+        let short_term = if self.num_blocks > 0 {
+            energy_to_lufs(self.last_blocks_energy(self.num_blocks))
+        } else {
+            util::MINUS_INFINITY_DB
+        };
+        levels.short_term.store(short_term, Ordering::Relaxed);
+        levels.integrated.store(self.integrated, Ordering::Relaxed);
+    }
+}
+
+/// The BS.1770 weight of `channel` in a layout with `channels` channels. The 5.1 and 7.1 layouts
+/// are ordered L, R, C, LFE followed by the surrounds, and the LFE channel isn't measured at all.
+fn loudness_weight(channel: usize, channels: usize) -> f32 {
+    match channel {
+        _ if channels < 6 => 1.0,
+        0..=2 => 1.0,
+        3 => 0.0,
+        _ => SURROUND_WEIGHT,
+    }
+}
+
+fn energy_to_lufs(energy: f32) -> f32 {
+    if energy > 0.0 {
+        -0.691 + 10.0 * energy.log10()
+    } else {
+        util::MINUS_INFINITY_DB
+    }
+}
+
+fn histogram_bin(loudness: f32) -> usize {
+    (((loudness - ABSOLUTE_GATE_LUFS) / HISTOGRAM_STEP_LU).max(0.0) as usize)
+        .min(HISTOGRAM_BINS - 1)
+}
@@ -0,0 +1,16 @@
+pub mod analyzer;
+pub mod biquad;
+pub mod crossfading_disperser;
+pub mod crossover;
+pub mod envelope;
+pub mod feedback;
+pub mod keytrack;
+pub mod lfo;
+pub mod meter;
+pub mod multiband;
+pub mod oversampling;
+pub mod pitch;
+pub mod response;
+pub mod spring;
+pub mod transient;
+pub mod zap;
@@ -0,0 +1,305 @@
+//! Splits the input into up to four bands, each with its own cascade.
+
+use i_am_dsp::ProcessContext as DspContext;
+use nih_plug::prelude::*;
+
+use crate::dsp::crossfading_disperser::CrossfadingDisperser;
+use crate::dsp::crossover::LinkwitzRiley;
+use crate::{MAX_AMOUNT, MAX_FREQUENCY, MAX_SPREAD, MIN_FREQUENCY, MIN_SPREAD};
+
+pub const MAX_BANDS: usize = 4;
+const MAX_CROSSOVERS: usize = MAX_BANDS - 1;
+
+const DEFAULT_CROSSOVERS: [f32; MAX_CROSSOVERS] = [150.0, 1200.0, 6000.0];
+const DEFAULT_FREQUENCIES: [f32; MAX_BANDS] = [60.0, 500.0, 3000.0, 10000.0];
+const DEFAULT_SPREADS: [f32; MAX_BANDS] = [60.0, 400.0, 1145.0, 2000.0];
+
+#[derive(Params)]
+pub struct MultibandParams {
+    /// With a single band the plain cascade is used and everything below is ignored.
+    #[id = "bands"]
+    pub bands: IntParam,
+
+    #[id = "crossover_low"]
+    pub crossover_low: FloatParam,
+
+    #[id = "crossover_mid"]
+    pub crossover_mid: FloatParam,
+
+    #[id = "crossover_high"]
+    pub crossover_high: FloatParam,
+
+    #[nested(array, group = "Band")]
+    pub band: [BandParams; MAX_BANDS],
+}
+
+#[derive(Params)]
+pub struct BandParams {
+    #[id = "frequency"]
+    pub frequency: FloatParam,
+
+    #[id = "spread"]
+    pub spread: FloatParam,
+
+    #[id = "amount"]
+    pub amount: IntParam,
+
+    #[id = "solo"]
+    pub solo: BoolParam,
+
+    #[id = "mute"]
+    pub mute: BoolParam,
+}
+
+impl Default for MultibandParams {
+    fn default() -> Self {
+        Self {
+            bands: IntParam::new(
+                "Bands",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_BANDS as i32,
+                },
+            ),
+
+            crossover_low: crossover_param("Crossover Low", DEFAULT_CROSSOVERS[0]),
+            crossover_mid: crossover_param("Crossover Mid", DEFAULT_CROSSOVERS[1]),
+            crossover_high: crossover_param("Crossover High", DEFAULT_CROSSOVERS[2]),
+
+            band: std::array::from_fn(BandParams::new),
+        }
+    }
+}
+
+impl MultibandParams {
+    /// The crossover frequencies, in the order they split the signal.
+    pub fn crossovers(&self) -> [f32; MAX_CROSSOVERS] {
+        [
+            self.crossover_low.value(),
+            self.crossover_mid.value(),
+            self.crossover_high.value(),
+        ]
+    }
+}
+
+impl BandParams {
+    fn new(idx: usize) -> Self {
+        let band = idx + 1;
+
+        Self {
+            frequency: FloatParam::new(
+                format!("Band {band} Frequency"),
+                DEFAULT_FREQUENCIES[idx],
+                FloatRange::Skewed {
+                    min: MIN_FREQUENCY,
+                    max: MAX_FREQUENCY,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+
+            spread: FloatParam::new(
+                format!("Band {band} Spread"),
+                DEFAULT_SPREADS[idx],
+                FloatRange::Skewed {
+                    min: MIN_SPREAD,
+                    max: MAX_SPREAD,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" Hz"),
+
+            amount: IntParam::new(
+                format!("Band {band} Amount"),
+                40,
+                IntRange::Linear {
+                    min: 0,
+                    max: MAX_AMOUNT,
+                },
+            ),
+
+            solo: BoolParam::new(format!("Band {band} Solo"), false),
+            mute: BoolParam::new(format!("Band {band} Mute"), false),
+        }
+    }
+}
+
+fn crossover_param(name: &str, default: f32) -> FloatParam {
+    FloatParam::new(
+        name,
+        default,
+        FloatRange::Skewed {
+            min: 40.0,
+            max: 16000.0,
+            factor: FloatRange::skew_factor(-2.0),
+        },
+    )
+    .with_unit(" Hz")
+    .with_value_to_string(formatters::v2s_f32_rounded(0))
+}
+
+/// How the modulation and the stereo settings move the bands of a single channel.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BandOffsets {
+    pub frequency_ratio: f32,
+    /// Added to every band's number of stages.
+    pub amount_offset: f32,
+}
+
+/// The crossovers and cascades for a single channel.
+struct ChannelBands {
+    splits: [LinkwitzRiley; MAX_CROSSOVERS],
+    /// `compensation[band][split]` gives `band` the phase shift of a split it didn't go through.
+    compensation: [[LinkwitzRiley; MAX_CROSSOVERS]; MAX_BANDS],
+    dispersers: [CrossfadingDisperser<1>; MAX_BANDS],
+}
+
+impl ChannelBands {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            splits: Default::default(),
+            compensation: Default::default(),
+            dispersers: std::array::from_fn(|_| CrossfadingDisperser::new(sample_rate)),
+        }
+    }
+
+    fn reset(&mut self) {
+        for crossover in self
+            .splits
+            .iter_mut()
+            .chain(self.compensation.iter_mut().flatten())
+        {
+            crossover.reset();
+        }
+        for disperser in &mut self.dispersers {
+            disperser.reset();
+        }
+    }
+}
+
+/// Every band goes through the splits below it and the one right above it. The lower bands get
+/// the remaining splits as allpasses, so with all cascades at zero amount every band ends up with
+/// the same phase and the bands sum back to a flat magnitude response.
+pub struct Multiband {
+    channels: Vec<ChannelBands>,
+    num_bands: usize,
+    /// The gain of each band after solo and mute.
+    gains: [f32; MAX_BANDS],
+}
+
+impl Multiband {
+    pub fn new(sample_rate: f32, channels: usize) -> Self {
+        Self {
+            channels: (0..channels)
+                .map(|_| ChannelBands::new(sample_rate))
+                .collect(),
+            num_bands: 1,
+            gains: [1.0; MAX_BANDS],
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for channel in &mut self.channels {
+            channel.reset();
+        }
+    }
+
+    /// Whether the input is split at all. Otherwise the plain cascade should be used instead.
+    pub fn enabled(&self) -> bool {
+        self.num_bands > 1
+    }
+
+    /// Update the crossovers and cascades. What applies to the plain cascade is passed in as a
+    /// spread ratio and per channel offsets, so it moves every band of that channel.
+    pub fn update(
+        &mut self,
+        params: &MultibandParams,
+        sample_rate: f32,
+        offsets: &[BandOffsets],
+        spread_ratio: f32,
+        crossfade: bool,
+    ) {
+        let num_bands = params.bands.value() as usize;
+        if num_bands != self.num_bands {
+            // The crossovers' state belongs to a different split, start over
+            self.reset();
+            self.num_bands = num_bands;
+        }
+        if !self.enabled() {
+            return;
+        }
+
+        let bands = &params.band[..num_bands];
+        let any_solo = bands.iter().any(|band| band.solo.value());
+        for (gain, band) in self.gains.iter_mut().zip(bands) {
+            let audible = !band.mute.value() && (!any_solo || band.solo.value());
+            *gain = if audible { 1.0 } else { 0.0 };
+        }
+
+        // The crossovers are independent parameters, so they need to be put in order first
+        let mut crossovers = params.crossovers();
+        let crossovers = &mut crossovers[..num_bands - 1];
+        crossovers.sort_by(f32::total_cmp);
+
+        for (channel, offsets) in self.channels.iter_mut().zip(offsets) {
+            for (split_idx, &frequency) in crossovers.iter().enumerate() {
+                channel.splits[split_idx].set_frequency(sample_rate, frequency);
+                for compensation in &mut channel.compensation {
+                    compensation[split_idx].set_frequency(sample_rate, frequency);
+                }
+            }
+
+            for (disperser, band) in channel.dispersers.iter_mut().zip(bands) {
+                let frequency = (band.frequency.value() * offsets.frequency_ratio)
+                    .clamp(MIN_FREQUENCY, MAX_FREQUENCY);
+                let spread = (band.spread.value() * spread_ratio).clamp(MIN_SPREAD, MAX_SPREAD);
+                let amount = (band.amount.value() as f32 + offsets.amount_offset)
+                    .round()
+                    .clamp(0.0, MAX_AMOUNT as f32) as usize;
+
+                disperser.set_filter_parameters(frequency, spread);
+                disperser.set_biquad_count(amount, crossfade);
+            }
+        }
+    }
+
+    pub fn process(
+        &mut self,
+        channel: usize,
+        sample: f32,
+        dsp_ctx: &mut Box<dyn DspContext>,
+    ) -> f32 {
+        let num_splits = self.num_bands - 1;
+        let bands = &mut self.channels[channel];
+
+        let mut output = 0.0;
+        let mut rest = sample;
+        for band_idx in 0..self.num_bands {
+            let mut band = if band_idx < num_splits {
+                let (low, high) = bands.splits[band_idx].split(rest);
+                rest = high;
+                low
+            } else {
+                rest
+            };
+
+            // Muted bands are still processed, so their filters stay in step with the others and
+            // they come back in without a click
+            for compensation in bands.compensation[band_idx]
+                .iter_mut()
+                .take(num_splits)
+                .skip(band_idx + 1)
+            {
+                band = compensation.allpass(band);
+            }
+
+            let mut frame = [band];
+            bands.dispersers[band_idx].process(&mut frame, dsp_ctx);
+            output += frame[0] * self.gains[band_idx];
+        }
+
+        output
+    }
+}
@@ -0,0 +1,294 @@
+//! Runs the cascades at two or four times the sample rate, so the allpass stages near the top of
+//! the frequency range don't get squashed against Nyquist.
+
+use nih_plug::prelude::*;
+
+use crate::dsp::biquad::Biquad;
+
+/// Every stage doubles the sample rate.
+pub const MAX_STAGES: usize = 2;
+pub const MAX_FACTOR: usize = 1 << MAX_STAGES;
+
+/// The length of the linear phase filters. `FIR_TAPS - 1` needs to be a multiple of four for the
+/// latency to be a whole number of samples with both stages.
+const FIR_TAPS: usize = 65;
+/// The minimum phase filters are eighth order Butterworth lowpasses made from this many biquads.
+const IIR_SECTIONS: usize = 4;
+/// The minimum phase filters' cutoff, relative to the lower of the two sample rates of a stage.
+const IIR_CUTOFF: f32 = 0.45;
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Oversampling {
+    #[id = "1x"]
+    #[name = "1x"]
+    X1,
+    #[id = "2x"]
+    #[name = "2x"]
+    X2,
+    #[id = "4x"]
+    #[name = "4x"]
+    X4,
+}
+
+impl Oversampling {
+    /// The number of times the sample rate gets doubled.
+    pub fn stages(self) -> usize {
+        match self {
+            Oversampling::X1 => 0,
+            Oversampling::X2 => 1,
+            Oversampling::X4 => 2,
+        }
+    }
+}
+
+/// The filters used to get to the higher sample rate and back.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversamplingFilter {
+    /// IIR filters. No latency, but a bit of phase shift near the top of the spectrum.
+    #[id = "minimum_phase"]
+    #[name = "Min Phase"]
+    MinimumPhase,
+    /// FIR filters. No phase shift, but they add latency.
+    #[id = "linear_phase"]
+    #[name = "Linear Phase"]
+    LinearPhase,
+}
+
+/// The latency the oversampling adds, in samples at the original sample rate.
+pub fn latency_samples(stages: usize, filter: OversamplingFilter) -> u32 {
+    if filter == OversamplingFilter::MinimumPhase {
+        return 0;
+    }
+
+    // Each stage filters once on the way up and once on the way down, at twice the rate of the
+    // stage before it
+    (1..=stages)
+        .map(|stage| ((FIR_TAPS - 1) >> stage) as u32)
+        .sum()
+}
+
+/// A linear phase halfband lowpass.
+#[derive(Clone, Copy)]
+struct Fir {
+    history: [f32; FIR_TAPS],
+    pos: usize,
+}
+
+impl Default for Fir {
+    fn default() -> Self {
+        Self {
+            history: [0.0; FIR_TAPS],
+            pos: 0,
+        }
+    }
+}
+
+impl Fir {
+    fn process(&mut self, sample: f32, coefficients: &[f32; FIR_TAPS]) -> f32 {
+        self.history[self.pos] = sample;
+        self.pos = (self.pos + 1) % FIR_TAPS;
+
+        // `pos` now points at the oldest sample
+        let (newer, older) = self.history.split_at(self.pos);
+        older
+            .iter()
+            .chain(newer)
+            .zip(coefficients.iter().rev())
+            .map(|(sample, coefficient)| sample * coefficient)
+            .sum()
+    }
+}
+
+/// The up and down filters for a single doubling of the sample rate.
+#[derive(Clone, Copy, Default)]
+struct Stage {
+    up_fir: Fir,
+    down_fir: Fir,
+    up_iir: [Biquad; IIR_SECTIONS],
+    down_iir: [Biquad; IIR_SECTIONS],
+}
+
+impl Stage {
+    /// `sample_rate` is the rate going into the stage, the filters run at twice that.
+    fn new(sample_rate: f32) -> Self {
+        let mut stage = Self::default();
+        for (idx, (up, down)) in stage
+            .up_iir
+            .iter_mut()
+            .zip(stage.down_iir.iter_mut())
+            .enumerate()
+        {
+            // The Q of each section of a Butterworth filter comes from its poles' angles
+            let angle = (2 * idx + 1) as f32 * std::f32::consts::PI / (4 * IIR_SECTIONS) as f32;
+            let q = (2.0 * angle.cos()).recip();
+            let cutoff = sample_rate * IIR_CUTOFF;
+            up.set_lowpass(sample_rate * 2.0, cutoff, q);
+            down.set_lowpass(sample_rate * 2.0, cutoff, q);
+        }
+
+        stage
+    }
+
+    fn reset(&mut self) {
+        self.up_fir = Fir::default();
+        self.down_fir = Fir::default();
+        for biquad in self.up_iir.iter_mut().chain(&mut self.down_iir) {
+            biquad.reset();
+        }
+    }
+
+    fn up(
+        &mut self,
+        sample: f32,
+        filter: OversamplingFilter,
+        coefficients: &[f32; FIR_TAPS],
+    ) -> f32 {
+        match filter {
+            OversamplingFilter::MinimumPhase => self
+                .up_iir
+                .iter_mut()
+                .fold(sample, |sample, biquad| biquad.process(sample)),
+            OversamplingFilter::LinearPhase => self.up_fir.process(sample, coefficients),
+        }
+    }
+
+    fn down(
+        &mut self,
+        sample: f32,
+        filter: OversamplingFilter,
+        coefficients: &[f32; FIR_TAPS],
+    ) -> f32 {
+        match filter {
+            OversamplingFilter::MinimumPhase => self
+                .down_iir
+                .iter_mut()
+                .fold(sample, |sample, biquad| biquad.process(sample)),
+            OversamplingFilter::LinearPhase => self.down_fir.process(sample, coefficients),
+        }
+    }
+}
+
+/// Per-channel up and downsampling in cascaded 2x stages.
+pub struct Oversampler {
+    /// `stages[channel][stage]`, the first stage goes from the original rate to twice that.
+    stages: Vec<[Stage; MAX_STAGES]>,
+    coefficients: [f32; FIR_TAPS],
+}
+
+impl Oversampler {
+    pub fn new(sample_rate: f32, channels: usize) -> Self {
+        Self {
+            stages: (0..channels)
+                .map(|_| std::array::from_fn(|stage| Stage::new(sample_rate * (1 << stage) as f32)))
+                .collect(),
+            coefficients: halfband_coefficients(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for stage in self.stages.iter_mut().flatten() {
+            stage.reset();
+        }
+    }
+
+    /// Turn one sample into `1 << num_stages` samples at the higher rate.
+    pub fn upsample(
+        &mut self,
+        channel: usize,
+        sample: f32,
+        num_stages: usize,
+        filter: OversamplingFilter,
+    ) -> [f32; MAX_FACTOR] {
+        let mut output = [0.0; MAX_FACTOR];
+        output[0] = sample;
+
+        let mut len = 1;
+        for stage in &mut self.stages[channel][..num_stages] {
+            let input = output;
+            for (idx, sample) in input[..len].iter().enumerate() {
+                // Zero stuffing halves the level, the factor of two makes up for that
+                output[idx * 2] = stage.up(sample * 2.0, filter, &self.coefficients);
+                output[idx * 2 + 1] = stage.up(0.0, filter, &self.coefficients);
+            }
+            len *= 2;
+        }
+
+        output
+    }
+
+    /// Turn the `1 << num_stages` samples from [`upsample()`][Self::upsample()] back into one.
+    pub fn downsample(
+        &mut self,
+        channel: usize,
+        mut samples: [f32; MAX_FACTOR],
+        num_stages: usize,
+        filter: OversamplingFilter,
+    ) -> f32 {
+        let mut len = 1 << num_stages;
+        for stage in self.stages[channel][..num_stages].iter_mut().rev() {
+            for idx in 0..len / 2 {
+                let sample = stage.down(samples[idx * 2], filter, &self.coefficients);
+                stage.down(samples[idx * 2 + 1], filter, &self.coefficients);
+                samples[idx] = sample;
+            }
+            len /= 2;
+        }
+
+        samples[0]
+    }
+}
+
+/// Delays a frame by the oversampling latency, so signals that skip the oversampled cascades stay
+/// lined up with the ones that go through them.
+pub struct LatencyDelay {
+    lines: Vec<[f32; FIR_TAPS]>,
+    pos: usize,
+}
+
+impl LatencyDelay {
+    pub fn new(channels: usize) -> Self {
+        Self {
+            lines: vec![[0.0; FIR_TAPS]; channels],
+            pos: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for line in &mut self.lines {
+            line.fill(0.0);
+        }
+    }
+
+    /// Delay every sample in `frame` by `latency` samples. Needs to be called once per sample.
+    pub fn process(&mut self, frame: &mut [f32], latency: u32) {
+        for (sample, line) in frame.iter_mut().zip(&mut self.lines) {
+            line[self.pos] = *sample;
+            *sample = line[(self.pos + FIR_TAPS - latency as usize) % FIR_TAPS];
+        }
+        self.pos = (self.pos + 1) % FIR_TAPS;
+    }
+}
+
+/// A Blackman windowed sinc lowpass at a quarter of the sample rate.
+fn halfband_coefficients() -> [f32; FIR_TAPS] {
+    let center = (FIR_TAPS - 1) as f32 / 2.0;
+    let mut coefficients: [f32; FIR_TAPS] = std::array::from_fn(|idx| {
+        let x = idx as f32 - center;
+        let sinc = if x == 0.0 {
+            0.5
+        } else {
+            (std::f32::consts::FRAC_PI_2 * x).sin() / (std::f32::consts::PI * x)
+        };
+
+        let phase = std::f32::consts::TAU * idx as f32 / (FIR_TAPS - 1) as f32;
+        let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+        sinc * window
+    });
+
+    let sum: f32 = coefficients.iter().sum();
+    for coefficient in &mut coefficients {
+        *coefficient /= sum;
+    }
+
+    coefficients
+}
@@ -0,0 +1,312 @@
+//! Detects the input's fundamental with the YIN algorithm, so the cascade can be tuned to it.
+
+use atomic_float::AtomicF32;
+use nih_plug::prelude::*;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// The input is decimated to roughly this rate first. The fundamentals worth tuning to are low
+/// enough, and it keeps the difference function cheap.
+const DETECTION_RATE: f32 = 11025.0;
+/// The number of decimated samples an estimate is based on.
+const WINDOW: usize = 1024;
+/// The longest period that's considered, which puts the lowest detectable pitch at about 21 Hz.
+const MAX_LAG: usize = WINDOW / 2;
+/// The number of decimated samples between two estimates.
+const HOP: usize = 256;
+/// The difference function is computed a few lags at a time while the next hop comes in, so the
+/// audio thread's load stays even instead of spiking once every hop.
+const LAGS_PER_SAMPLE: usize = MAX_LAG.div_ceil(HOP);
+/// YIN's absolute threshold. Lower values reject more unclear estimates.
+const YIN_THRESHOLD: f32 = 0.15;
+/// Windows quieter than this don't produce an estimate.
+const SILENCE_DB: f32 = -50.0;
+
+/// How long learning listens to the input.
+const LEARN_SECONDS: f32 = 3.0;
+/// Enough room for all estimates made while learning, even at the highest decimated rates.
+const MAX_LEARN_ESTIMATES: usize = 512;
+/// The spread set by learning, relative to the detected fundamental.
+pub const LEARN_SPREAD_RATIO: f32 = 0.5;
+
+#[derive(Params)]
+pub struct PitchParams {
+    /// Continuously tune the cascade to the detected pitch.
+    #[id = "pitch_follow"]
+    pub follow: BoolParam,
+
+    /// Whether learning also sets the spread around the detected fundamental.
+    #[id = "pitch_learn_spread"]
+    pub learn_spread: BoolParam,
+
+    #[id = "pitch_follow_glide"]
+    pub glide: FloatParam,
+}
+
+impl Default for PitchParams {
+    fn default() -> Self {
+        Self {
+            follow: BoolParam::new("Pitch Follow", false),
+            learn_spread: BoolParam::new("Learn Spread", false),
+
+            glide: FloatParam::new(
+                "Pitch Follow Glide",
+                30.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 1000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+        }
+    }
+}
+
+/// Shared between the editor, which starts learning and applies the result to the parameters, and
+/// the audio thread, which does the actual listening.
+#[derive(Default)]
+pub(crate) struct PitchLearn {
+    requested: AtomicBool,
+    learning: AtomicBool,
+    frequency: AtomicF32,
+    /// Incremented every time learning finds a pitch, so the editor can tell there's a new one.
+    generation: AtomicU32,
+    /// The generation the editor last applied to the parameters.
+    applied_generation: AtomicU32,
+}
+
+impl PitchLearn {
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_learning(&self) -> bool {
+        self.learning.load(Ordering::Relaxed)
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    pub fn applied_generation(&self) -> u32 {
+        self.applied_generation.load(Ordering::Relaxed)
+    }
+
+    pub fn set_applied_generation(&self, generation: u32) {
+        self.applied_generation.store(generation, Ordering::Relaxed);
+    }
+
+    /// The frequency found by the last successful learn.
+    pub fn frequency(&self) -> f32 {
+        self.frequency.load(Ordering::Relaxed)
+    }
+}
+
+/// The audio thread's side of learning. Collects estimates for [`LEARN_SECONDS`] and publishes
+/// their median.
+pub struct PitchLearner {
+    remaining_samples: u32,
+    estimates: [f32; MAX_LEARN_ESTIMATES],
+    num_estimates: usize,
+}
+
+impl Default for PitchLearner {
+    fn default() -> Self {
+        Self {
+            remaining_samples: 0,
+            estimates: [0.0; MAX_LEARN_ESTIMATES],
+            num_estimates: 0,
+        }
+    }
+}
+
+impl PitchLearner {
+    /// Start learning if the editor asked for it.
+    pub fn poll(&mut self, shared: &PitchLearn, sample_rate: f32) {
+        if shared.requested.swap(false, Ordering::Relaxed) {
+            self.remaining_samples = (LEARN_SECONDS * sample_rate) as u32;
+            self.num_estimates = 0;
+            shared.learning.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.remaining_samples > 0
+    }
+
+    pub fn add_estimate(&mut self, frequency: f32) {
+        if self.is_active() && self.num_estimates < MAX_LEARN_ESTIMATES {
+            self.estimates[self.num_estimates] = frequency;
+            self.num_estimates += 1;
+        }
+    }
+
+    /// Count down one sample, and publish the result once the time is up.
+    pub fn advance(&mut self, shared: &PitchLearn) {
+        if self.remaining_samples == 0 {
+            return;
+        }
+
+        self.remaining_samples -= 1;
+        if self.remaining_samples == 0 {
+            let estimates = &mut self.estimates[..self.num_estimates];
+            if !estimates.is_empty() {
+                estimates.sort_unstable_by(f32::total_cmp);
+                shared
+                    .frequency
+                    .store(estimates[estimates.len() / 2], Ordering::Relaxed);
+                shared.generation.fetch_add(1, Ordering::Release);
+            }
+            shared.learning.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+pub struct PitchDetector {
+    /// The number of input samples that get averaged into one decimated sample.
+    decimation: usize,
+    decimation_counter: usize,
+    decimation_sum: f32,
+    detection_rate: f32,
+
+    /// The last [`WINDOW`] decimated samples.
+    history: [f32; WINDOW],
+    pos: usize,
+    samples_until_estimate: usize,
+
+    /// The history in chronological order as of the last hop, and its difference function.
+    frame: [f32; WINDOW],
+    difference: [f32; MAX_LAG + 1],
+    /// The next lag of the difference function to compute, while one is being computed.
+    next_lag: Option<usize>,
+    running_sum: f32,
+}
+
+impl PitchDetector {
+    pub fn new(sample_rate: f32) -> Self {
+        let decimation = (sample_rate / DETECTION_RATE).round().max(1.0) as usize;
+
+        Self {
+            decimation,
+            decimation_counter: 0,
+            decimation_sum: 0.0,
+            detection_rate: sample_rate / decimation as f32,
+
+            history: [0.0; WINDOW],
+            pos: 0,
+            samples_until_estimate: HOP,
+
+            frame: [0.0; WINDOW],
+            difference: [0.0; MAX_LAG + 1],
+            next_lag: None,
+            running_sum: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.decimation_counter = 0;
+        self.decimation_sum = 0.0;
+        self.history.fill(0.0);
+        self.samples_until_estimate = HOP;
+        self.next_lag = None;
+    }
+
+    /// Feed the detector the next (mono) input sample. Returns a new estimate every [`HOP`]
+    /// decimated samples, if the input had a clear enough pitch. Each estimate is for the window
+    /// that ended a hop earlier, as that's how long computing it takes.
+    pub fn process(&mut self, sample: f32) -> Option<f32> {
+        self.decimation_sum += sample;
+        self.decimation_counter += 1;
+        if self.decimation_counter < self.decimation {
+            return None;
+        }
+
+        self.history[self.pos] = self.decimation_sum / self.decimation as f32;
+        self.pos = (self.pos + 1) % WINDOW;
+        self.decimation_counter = 0;
+        self.decimation_sum = 0.0;
+
+        let estimate = match self.next_lag {
+            Some(first_lag) => self.continue_estimate(first_lag),
+            None => None,
+        };
+
+        self.samples_until_estimate -= 1;
+        if self.samples_until_estimate == 0 {
+            self.samples_until_estimate = HOP;
+            self.start_estimate();
+        }
+
+        estimate
+    }
+
+    /// Take a snapshot of the history to compute the next estimate from.
+    fn start_estimate(&mut self) {
+        let (newer, older) = self.history.split_at(self.pos);
+        self.frame[..older.len()].copy_from_slice(older);
+        self.frame[older.len()..].copy_from_slice(newer);
+
+        let integration_window = WINDOW - MAX_LAG;
+        let mean_square = self.frame[..integration_window]
+            .iter()
+            .map(|sample| sample * sample)
+            .sum::<f32>()
+            / integration_window as f32;
+        if mean_square < util::db_to_gain(SILENCE_DB).powi(2) {
+            self.next_lag = None;
+            return;
+        }
+
+        self.difference[0] = 1.0;
+        self.running_sum = 0.0;
+        self.next_lag = Some(1);
+    }
+
+    /// Compute the next few lags of the cumulative mean normalized difference function, and the
+    /// estimate once it's complete.
+    fn continue_estimate(&mut self, first_lag: usize) -> Option<f32> {
+        let integration_window = WINDOW - MAX_LAG;
+        let last_lag = (first_lag + LAGS_PER_SAMPLE - 1).min(MAX_LAG);
+        for lag in first_lag..=last_lag {
+            let difference: f32 = self.frame[..integration_window]
+                .iter()
+                .zip(&self.frame[lag..lag + integration_window])
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum();
+            self.running_sum += difference;
+            self.difference[lag] = if self.running_sum > 0.0 {
+                difference * lag as f32 / self.running_sum
+            } else {
+                1.0
+            };
+        }
+
+        if last_lag < MAX_LAG {
+            self.next_lag = Some(last_lag + 1);
+            return None;
+        }
+        self.next_lag = None;
+
+        // The first dip below the threshold, followed down to its minimum
+        let mut lag = (2..MAX_LAG).find(|&lag| self.difference[lag] < YIN_THRESHOLD)?;
+        while lag + 1 < MAX_LAG && self.difference[lag + 1] < self.difference[lag] {
+            lag += 1;
+        }
+
+        // Parabolic interpolation around the minimum for a sub-sample period
+        let (previous, current, next) = (
+            self.difference[lag - 1],
+            self.difference[lag],
+            self.difference[lag + 1],
+        );
+        let curvature = previous - 2.0 * current + next;
+        let offset = if curvature > 0.0 {
+            (0.5 * (previous - next) / curvature).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+
+        Some(self.detection_rate / (lag as f32 + offset))
+    }
+}
@@ -0,0 +1,235 @@
+//! The phase and group delay response of the disperser cascade, for drawing it in the editor.
+
+use std::f32::consts::TAU;
+
+/// The response of the cascade at a single frequency.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CascadeResponse {
+    /// The phase shift in radians, unwrapped, so it keeps going down with every stage.
+    pub phase: f32,
+    /// The group delay in seconds.
+    pub group_delay: f32,
+}
+
+/// The response at `at_frequency` of a cascade of `amount` second order allpass stages centred on
+/// `frequency`, with `spread` as their bandwidth in Hz, running at `sample_rate`.
+pub fn cascade_response(
+    frequency: f32,
+    spread: f32,
+    amount: usize,
+    sample_rate: f32,
+    at_frequency: f32,
+) -> CascadeResponse {
+    let nyquist = sample_rate / 2.0;
+    let frequency = frequency.min(nyquist * 0.999);
+    let q = frequency / spread;
+    let omega_0 = TAU * frequency / sample_rate;
+    let alpha = omega_0.sin() / (2.0 * q);
+    let denominator = [1.0 + alpha, -2.0 * omega_0.cos(), 1.0 - alpha];
+
+    // An allpass' numerator is its denominator reversed, so both the phase and the group delay
+    // follow from the denominator `D` alone: `phase = -2 * omega - 2 * arg(D)` and
+    // `group_delay = 2 - 2 * group_delay(D)`, the latter in samples
+    let omega = TAU * at_frequency.min(nyquist) / sample_rate;
+    let (mut real, mut imaginary) = (0.0, 0.0);
+    let (mut weighted_real, mut weighted_imaginary) = (0.0, 0.0);
+    for (k, coefficient) in denominator.iter().enumerate() {
+        let (sin, cos) = (k as f32 * omega).sin_cos();
+        real += coefficient * cos;
+        imaginary -= coefficient * sin;
+        weighted_real += k as f32 * coefficient * cos;
+        weighted_imaginary -= k as f32 * coefficient * sin;
+    }
+    let magnitude_squared = real * real + imaginary * imaginary;
+    let denominator_delay =
+        (weighted_real * real + weighted_imaginary * imaginary) / magnitude_squared;
+
+    // `arg(D)` stays within (-pi, pi) for these filters, but the stage's own phase needs to be
+    // unwrapped past `-pi` at the centre frequency. At Nyquist `omega` can round to just above or
+    // just below pi depending on the sample rate, so the full cycle there is set directly.
+    let mut stage_phase = -2.0 * omega - 2.0 * imaginary.atan2(real);
+    while stage_phase > 0.0 {
+        stage_phase -= TAU;
+    }
+    while stage_phase < -TAU {
+        stage_phase += TAU;
+    }
+    if at_frequency >= nyquist {
+        stage_phase = -TAU;
+    }
+
+    CascadeResponse {
+        phase: stage_phase * amount as f32,
+        group_delay: (2.0 - 2.0 * denominator_delay) * amount as f32 / sample_rate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use i_am_dsp::Effect;
+    use i_am_dsp::prelude::Disperser;
+
+    use super::*;
+    use crate::dsp_context;
+
+    /// `(frequency, spread, amount, sample_rate)`
+    const CASCADES: [(f32, f32, usize, f32); 6] = [
+        (100.0, 50.0, 1, 44100.0),
+        (500.0, 400.0, 10, 48000.0),
+        (2000.0, 2000.0, 40, 48000.0),
+        (3000.0, 100.0, 4, 96000.0),
+        (8000.0, 1000.0, 100, 44100.0),
+        (15000.0, 0.1, 2, 192000.0),
+    ];
+
+    /// The group delay should match the phase's slope, measured over a small step around
+    /// `at_frequency`.
+    #[test]
+    fn group_delay_is_phase_slope() {
+        for (frequency, spread, amount, sample_rate) in CASCADES {
+            for at_fraction in [0.01, 0.1, 0.3, 0.5, 0.7, 0.9] {
+                let at_frequency = sample_rate / 2.0 * at_fraction;
+                let step = at_frequency * 1e-3;
+
+                let below =
+                    cascade_response(frequency, spread, amount, sample_rate, at_frequency - step);
+                let above =
+                    cascade_response(frequency, spread, amount, sample_rate, at_frequency + step);
+                let response =
+                    cascade_response(frequency, spread, amount, sample_rate, at_frequency);
+
+                let slope_delay = -(above.phase - below.phase) / (TAU * 2.0 * step);
+                let tolerance = 1e-2 * response.group_delay.abs() + 1e-6;
+                assert!(
+                    (response.group_delay - slope_delay).abs() <= tolerance,
+                    "{frequency} Hz, {spread} Hz spread, {amount} stages at {sample_rate} Hz, \
+                     measured at {at_frequency} Hz: {} s group delay, {slope_delay} s phase slope",
+                    response.group_delay
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn dc_and_nyquist() {
+        for (frequency, spread, amount, sample_rate) in CASCADES {
+            let nyquist = sample_rate / 2.0;
+            let dc = cascade_response(frequency, spread, amount, sample_rate, 0.0);
+            let at_nyquist = cascade_response(frequency, spread, amount, sample_rate, nyquist);
+            let past_nyquist =
+                cascade_response(frequency, spread, amount, sample_rate, sample_rate);
+
+            // Every stage leaves DC alone and turns Nyquist around by a full cycle
+            assert_eq!(dc.phase, 0.0);
+            assert_eq!(at_nyquist.phase, -TAU * amount as f32);
+            assert_eq!(past_nyquist, at_nyquist);
+
+            for response in [dc, at_nyquist] {
+                assert!(response.group_delay.is_finite());
+                assert!(response.group_delay > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_amount_is_transparent() {
+        for (frequency, spread, _, sample_rate) in CASCADES {
+            for at_fraction in [0.0, 0.01, 0.25, 0.5, 0.75, 1.0] {
+                let at_frequency = sample_rate / 2.0 * at_fraction;
+                let response = cascade_response(frequency, spread, 0, sample_rate, at_frequency);
+
+                assert_eq!(response.phase, 0.0);
+                assert_eq!(response.group_delay, 0.0);
+            }
+        }
+    }
+
+    /// `omega` at Nyquist lands just below pi at 44.1 kHz and on pi at 48 kHz, the phase there
+    /// should be a full cycle per stage either way.
+    #[test]
+    fn nyquist_at_common_sample_rates() {
+        for sample_rate in [44100.0, 48000.0, 88200.0, 96000.0, 176400.0, 192000.0] {
+            let nyquist = sample_rate / 2.0;
+            let omega = TAU * nyquist / sample_rate;
+            let at_nyquist = cascade_response(1000.0, 200.0, 3, sample_rate, nyquist);
+            let below_nyquist = cascade_response(1000.0, 200.0, 3, sample_rate, nyquist * 0.999);
+
+            assert_eq!(
+                at_nyquist.phase,
+                -TAU * 3.0,
+                "{sample_rate} Hz, omega {omega}"
+            );
+            assert!(
+                (below_nyquist.phase - at_nyquist.phase).abs() < 1e-2,
+                "{sample_rate} Hz: {} just below Nyquist",
+                below_nyquist.phase
+            );
+        }
+    }
+
+    /// The phase and group delay of an actual cascade's impulse response should match the
+    /// computed ones.
+    #[test]
+    fn matches_disperser() {
+        const IMPULSE_LENGTH: usize = 16384;
+
+        for (frequency, spread, amount, sample_rate) in [CASCADES[0], CASCADES[1], CASCADES[3]] {
+            let mut disperser = Disperser::<1>::new(sample_rate as usize);
+            disperser.set_biquad_count(amount);
+            disperser.set_filter_parameters(frequency, spread);
+
+            let mut dsp_ctx = dsp_context(sample_rate);
+            let other_inputs: &[&[f32; 1]] = &[];
+            let impulse_response: Vec<f64> = (0..IMPULSE_LENGTH)
+                .map(|idx| {
+                    let mut frame = [if idx == 0 { 1.0 } else { 0.0 }];
+                    disperser.process(&mut frame, other_inputs, &mut dsp_ctx);
+                    frame[0] as f64
+                })
+                .collect();
+
+            for at_frequency in [
+                frequency * 0.5,
+                frequency,
+                frequency * 2.0,
+                sample_rate * 0.15,
+            ] {
+                // The DFT at `at_frequency`, and the same with every sample weighted by its time,
+                // which gives the group delay in samples as `Re(weighted / spectrum)`
+                let omega = std::f64::consts::TAU * at_frequency as f64 / sample_rate as f64;
+                let (mut real, mut imaginary) = (0.0, 0.0);
+                let (mut weighted_real, mut weighted_imaginary) = (0.0, 0.0);
+                for (n, sample) in impulse_response.iter().enumerate() {
+                    let (sin, cos) = (n as f64 * omega).sin_cos();
+                    real += sample * cos;
+                    imaginary -= sample * sin;
+                    weighted_real += n as f64 * sample * cos;
+                    weighted_imaginary -= n as f64 * sample * sin;
+                }
+                let measured_phase = imaginary.atan2(real) as f32;
+                let measured_delay = ((weighted_real * real + weighted_imaginary * imaginary)
+                    / (real * real + imaginary * imaginary))
+                    as f32
+                    / sample_rate;
+
+                let response =
+                    cascade_response(frequency, spread, amount, sample_rate, at_frequency);
+                let phase_error = (response.phase - measured_phase).rem_euclid(TAU);
+                let phase_error = phase_error.min(TAU - phase_error);
+                assert!(
+                    phase_error < 1e-2,
+                    "{frequency} Hz, {spread} Hz spread, {amount} stages at {sample_rate} Hz, \
+                     measured at {at_frequency} Hz: {} phase, {measured_phase} measured",
+                    response.phase
+                );
+                assert!(
+                    (response.group_delay - measured_delay).abs()
+                        <= 1e-2 * measured_delay.abs() + 1.0 / sample_rate,
+                    "{frequency} Hz, {spread} Hz spread, {amount} stages at {sample_rate} Hz, \
+                     measured at {at_frequency} Hz: {} s group delay, {measured_delay} s measured",
+                    response.group_delay
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! A spring reverb tank built from disperser cascades inside delay lines.
+
+use i_am_dsp::ProcessContext as DspContext;
+use nih_plug::prelude::*;
+
+use crate::dsp::crossfading_disperser::CrossfadingDisperser;
+use crate::dsp::feedback::TAIL_SILENCE_DB;
+use crate::{MAX_FREQUENCY, MIN_FREQUENCY};
+
+pub const MAX_SPRINGS: usize = 4;
+/// The longest spring delay. Needed to allocate the delay lines up front.
+const MAX_DELAY_MS: f32 = 200.0;
+/// Every extra spring is a bit longer and a bit tighter than the one before it, so the springs
+/// don't ring in unison.
+const SPRING_DELAY_SPREAD: f32 = 0.13;
+const SPRING_TENSION_SPREAD: f32 = 0.07;
+/// Odd channels get slightly longer springs for some stereo width.
+const CHANNEL_DELAY_SPREAD: f32 = 0.03;
+
+#[derive(Params)]
+pub struct SpringParams {
+    /// Replaces the plain cascade with the spring tank.
+    #[id = "spring"]
+    pub enabled: BoolParam,
+
+    /// Tighter springs chirp at higher frequencies. Sets the cascades' frequency.
+    #[id = "spring_tension"]
+    pub tension: FloatParam,
+
+    /// The time it takes for the tank to decay by 60 dB.
+    #[id = "spring_decay"]
+    pub decay: FloatParam,
+
+    /// The delay of a single trip through the spring.
+    #[id = "spring_length"]
+    pub length: FloatParam,
+
+    #[id = "spring_count"]
+    pub springs: IntParam,
+}
+
+impl Default for SpringParams {
+    fn default() -> Self {
+        Self {
+            enabled: BoolParam::new("Spring Mode", false),
+
+            tension: FloatParam::new(
+                "Spring Tension",
+                0.4,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            decay: FloatParam::new(
+                "Spring Decay",
+                2.0,
+                FloatRange::Skewed {
+                    min: 0.2,
+                    max: 10.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" s")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            length: FloatParam::new(
+                "Spring Length",
+                40.0,
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: MAX_DELAY_MS / (1.0 + SPRING_DELAY_SPREAD * MAX_SPRINGS as f32),
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            springs: IntParam::new(
+                "Springs",
+                2,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_SPRINGS as i32,
+                },
+            ),
+        }
+    }
+}
+
+impl SpringParams {
+    /// The cascades' centre frequency for the current tension, from 500 Hz to 8 kHz.
+    pub fn frequency(&self) -> f32 {
+        500.0 * 2.0f32.powf(self.tension.value() * 4.0)
+    }
+}
+
+/// A single spring: a disperser cascade inside a damped feedback delay.
+struct Spring {
+    disperser: CrossfadingDisperser<1>,
+    delay_line: Vec<f32>,
+    write_pos: usize,
+    delay_samples: usize,
+    damping_state: f32,
+}
+
+impl Spring {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            disperser: CrossfadingDisperser::new(sample_rate),
+            delay_line: vec![0.0; (sample_rate * MAX_DELAY_MS / 1000.0).ceil() as usize + 1],
+            write_pos: 0,
+            delay_samples: 1,
+            damping_state: 0.0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.disperser.reset();
+        self.delay_line.fill(0.0);
+        self.damping_state = 0.0;
+    }
+
+    fn process(
+        &mut self,
+        sample: f32,
+        feedback: f32,
+        damping_weight: f32,
+        dsp_ctx: &mut Box<dyn DspContext>,
+    ) -> f32 {
+        let len = self.delay_line.len();
+        let delayed = self.delay_line[(self.write_pos + len - self.delay_samples) % len];
+
+        self.damping_state = self.damping_state * damping_weight + delayed * (1.0 - damping_weight);
+        let mut frame = [sample + (self.damping_state * feedback).tanh()];
+        self.disperser.process(&mut frame, dsp_ctx);
+
+        self.delay_line[self.write_pos] = frame[0];
+        self.write_pos = (self.write_pos + 1) % len;
+
+        delayed
+    }
+}
+
+/// [`MAX_SPRINGS`] springs for every channel, of which the first `springs` are used.
+pub struct SpringTank {
+    springs: Vec<[Spring; MAX_SPRINGS]>,
+    active_springs: usize,
+    feedback: f32,
+    damping_weight: f32,
+    /// Every channel's peak level going into and coming out of the tank, falling at the rate the
+    /// tank decays.
+    levels: Vec<f32>,
+    level_decay_weight: f32,
+    enabled: bool,
+}
+
+impl SpringTank {
+    pub fn new(sample_rate: f32, channels: usize) -> Self {
+        Self {
+            springs: (0..channels)
+                .map(|_| std::array::from_fn(|_| Spring::new(sample_rate)))
+                .collect(),
+            active_springs: 1,
+            feedback: 0.0,
+            damping_weight: 0.0,
+            levels: vec![0.0; channels],
+            level_decay_weight: 0.0,
+            enabled: false,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for spring in self.springs.iter_mut().flatten() {
+            spring.reset();
+        }
+        self.levels.fill(0.0);
+    }
+
+    /// The springs don't run while spring mode is off. Clears what was left in them when it's
+    /// switched back on, so an old tail doesn't suddenly start playing again.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled && !self.enabled {
+            self.reset();
+        }
+        self.enabled = enabled;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Update all springs for the current parameters. `amount`, `spread` and `crossfade` are shared
+    /// with the plain cascade, `damping_cutoff` with the plain feedback path.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        params: &SpringParams,
+        sample_rate: f32,
+        spread: f32,
+        amount: usize,
+        crossfade: bool,
+        damping_cutoff: f32,
+        group_delay_samples: f32,
+    ) {
+        self.active_springs = params.springs.value() as usize;
+        self.damping_weight = (-std::f32::consts::TAU * damping_cutoff / sample_rate).exp();
+
+        let base_delay = sample_rate * params.length.value() / 1000.0;
+        let base_frequency = params.frequency();
+        for (channel, springs) in self.springs.iter_mut().enumerate() {
+            let channel_spread = if channel % 2 == 1 {
+                1.0 + CHANNEL_DELAY_SPREAD
+            } else {
+                1.0
+            };
+
+            for (idx, spring) in springs.iter_mut().enumerate() {
+                let delay = base_delay * (1.0 + SPRING_DELAY_SPREAD * idx as f32) * channel_spread;
+                spring.delay_samples = (delay as usize).clamp(1, spring.delay_line.len() - 1);
+
+                let frequency = (base_frequency * (1.0 + SPRING_TENSION_SPREAD * idx as f32))
+                    .clamp(MIN_FREQUENCY, MAX_FREQUENCY);
+                spring.disperser.set_filter_parameters(frequency, spread);
+                spring.disperser.set_biquad_count(amount, crossfade);
+            }
+        }
+
+        // The loop gain that makes one round trip (the delay plus the cascade's own delay) decay by
+        // the right amount for a 60 dB drop over the decay time
+        let round_trip_seconds = (base_delay + group_delay_samples) / sample_rate;
+        self.feedback = 10.0f32.powf(-3.0 * round_trip_seconds / params.decay.value());
+        self.level_decay_weight = 10.0f32.powf(-3.0 / (sample_rate * params.decay.value()));
+    }
+
+    pub fn process(
+        &mut self,
+        channel: usize,
+        sample: f32,
+        dsp_ctx: &mut Box<dyn DspContext>,
+    ) -> f32 {
+        let springs = &mut self.springs[channel][..self.active_springs];
+        let gain = (springs.len() as f32).recip();
+
+        let output = springs
+            .iter_mut()
+            .map(|spring| spring.process(sample, self.feedback, self.damping_weight, dsp_ctx))
+            .sum::<f32>()
+            * gain;
+
+        let level = &mut self.levels[channel];
+        *level = (*level * self.level_decay_weight)
+            .max(sample.abs())
+            .max(output.abs());
+
+        output
+    }
+
+    /// Estimate how many samples it takes for the tank to decay into silence from its current
+    /// level. Zero once it's silent, so the host can suspend the plugin.
+    pub fn tail_samples(&self, params: &SpringParams, sample_rate: f32) -> u32 {
+        let level = self
+            .levels
+            .iter()
+            .fold(0.0f32, |peak, level| peak.max(*level));
+        let level_db = util::gain_to_db(level);
+        if level_db <= TAIL_SILENCE_DB {
+            return 0;
+        }
+
+        // The decay time is for 60 dB, and whatever is still in the delay lines has one more
+        // trip to go
+        let decay_seconds = params.decay.value() * (level_db - TAIL_SILENCE_DB) / 60.0;
+        let delay_seconds = MAX_DELAY_MS / 1000.0;
+        ((decay_seconds + delay_seconds) * sample_rate).ceil() as u32
+    }
+}
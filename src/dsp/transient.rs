@@ -0,0 +1,125 @@
+//! Detects transients by comparing a fast and a slow envelope of the input level. Used to trigger
+//! the zap and to let attacks bypass the cascade.
+
+use nih_plug::prelude::*;
+
+const FAST_ENVELOPE_MS: f32 = 1.0;
+const SLOW_ENVELOPE_MS: f32 = 50.0;
+/// After a trigger the detector ignores new transients for this long, so one hit only fires once.
+const HOLDOFF_MS: f32 = 50.0;
+/// Anything quieter than this never counts as a transient.
+const SILENCE_DB: f32 = -60.0;
+/// How far the fast envelope can rise above the slow one before the excess counts as transient, at
+/// the lowest and highest sensitivity.
+const SUSTAIN_MARGIN_DB: (f32, f32) = (12.0, 0.0);
+
+#[derive(Params)]
+pub struct TransientParams {
+    /// Splits the input by level: whatever rises above the recent level during an attack bypasses
+    /// the cascade, the rest goes through it. The two parts always add up to the input. This is a
+    /// split of the level over time, not of the signal's components, so the sustain that keeps
+    /// ringing underneath an attack bypasses the cascade along with it.
+    #[id = "transient_preserve"]
+    pub preserve: BoolParam,
+
+    #[id = "transient_sensitivity"]
+    pub sensitivity: FloatParam,
+
+    /// How much of the bypassed part gets added back on top of the dispersed rest.
+    #[id = "transient_amount"]
+    pub amount: FloatParam,
+}
+
+impl Default for TransientParams {
+    fn default() -> Self {
+        Self {
+            preserve: BoolParam::new("Transient Preserve", false),
+
+            sensitivity: FloatParam::new(
+                "Transient Sensitivity",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            amount: FloatParam::new(
+                "Transient Amount",
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 2.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+}
+
+pub struct TransientDetector {
+    fast_envelope: f32,
+    slow_envelope: f32,
+    fast_weight: f32,
+    slow_weight: f32,
+
+    holdoff_samples: u32,
+    holdoff_remaining: u32,
+}
+
+impl TransientDetector {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            fast_envelope: 0.0,
+            slow_envelope: 0.0,
+            fast_weight: (-1.0 / (sample_rate * FAST_ENVELOPE_MS / 1000.0)).exp(),
+            slow_weight: (-1.0 / (sample_rate * SLOW_ENVELOPE_MS / 1000.0)).exp(),
+
+            holdoff_samples: (sample_rate * HOLDOFF_MS / 1000.0) as u32,
+            holdoff_remaining: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.fast_envelope = 0.0;
+        self.slow_envelope = 0.0;
+        self.holdoff_remaining = 0;
+    }
+
+    /// Feed the detector the next absolute input level. Returns `true` when the fast envelope jumps
+    /// more than `threshold_db` above the slow one.
+    pub fn process(&mut self, level: f32, threshold_db: f32) -> bool {
+        self.fast_envelope =
+            self.fast_envelope * self.fast_weight + level * (1.0 - self.fast_weight);
+        self.slow_envelope =
+            self.slow_envelope * self.slow_weight + level * (1.0 - self.slow_weight);
+
+        if self.holdoff_remaining > 0 {
+            self.holdoff_remaining -= 1;
+            return false;
+        }
+
+        let triggered = self.fast_envelope > util::db_to_gain(SILENCE_DB)
+            && self.fast_envelope > self.slow_envelope * util::db_to_gain(threshold_db);
+        if triggered {
+            self.holdoff_remaining = self.holdoff_samples;
+        }
+
+        triggered
+    }
+
+    /// The fraction of the current input that's above the sustain level, from 0 to 1. The
+    /// sustain level is the slow envelope plus a margin, so the rest of the input stays at that
+    /// level during an attack. Higher `sensitivity` values use a smaller margin.
+    pub fn transient_amount(&self, sensitivity: f32) -> f32 {
+        if self.fast_envelope <= util::db_to_gain(SILENCE_DB) {
+            return 0.0;
+        }
+
+        let (least_sensitive_db, most_sensitive_db) = SUSTAIN_MARGIN_DB;
+        let margin_db = least_sensitive_db + (most_sensitive_db - least_sensitive_db) * sensitivity;
+        let sustain_level = self.slow_envelope * util::db_to_gain(margin_db);
+
+        (1.0 - sustain_level / self.fast_envelope).clamp(0.0, 1.0)
+    }
+}
@@ -0,0 +1,175 @@
+//! A triggered frequency sweep, the classic disperser "zap".
+
+use nih_plug::prelude::*;
+
+use crate::{MAX_FREQUENCY, MIN_FREQUENCY, glide_weight};
+
+/// Once a sweep is done the frequency glides from the end frequency back to the parameter's.
+const RELEASE_GLIDE_MS: f32 = 40.0;
+/// The release is over once it's this close to the parameter's frequency.
+const RELEASE_DONE_OCTAVES: f32 = 0.001;
+
+/// What starts a new sweep.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZapTrigger {
+    #[id = "off"]
+    Off,
+    /// Every MIDI note on.
+    #[id = "midi"]
+    Midi,
+    /// Every transient detected in the input.
+    #[id = "transient"]
+    Transient,
+}
+
+#[derive(Params)]
+pub struct ZapParams {
+    #[id = "zap_trigger"]
+    pub trigger: EnumParam<ZapTrigger>,
+
+    #[id = "zap_start"]
+    pub start_frequency: FloatParam,
+
+    #[id = "zap_end"]
+    pub end_frequency: FloatParam,
+
+    #[id = "zap_time"]
+    pub time: FloatParam,
+
+    /// Bends the sweep. Positive values move away from the start frequency quickly and then slow
+    /// down, negative values do the opposite.
+    #[id = "zap_curve"]
+    pub curve: FloatParam,
+
+    /// How far the input has to jump above its recent level to count as a transient.
+    #[id = "zap_transient_threshold"]
+    pub transient_threshold: FloatParam,
+}
+
+impl Default for ZapParams {
+    fn default() -> Self {
+        Self {
+            trigger: EnumParam::new("Zap Trigger", ZapTrigger::Off),
+
+            start_frequency: zap_frequency_param("Zap Start", 8000.0),
+            end_frequency: zap_frequency_param("Zap End", 60.0),
+
+            time: FloatParam::new(
+                "Zap Time",
+                150.0,
+                FloatRange::Skewed {
+                    min: 5.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            curve: FloatParam::new(
+                "Zap Curve",
+                0.5,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            transient_threshold: FloatParam::new(
+                "Zap Transient Threshold",
+                6.0,
+                FloatRange::Linear {
+                    min: 1.0,
+                    max: 24.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+        }
+    }
+}
+
+fn zap_frequency_param(name: &str, default: f32) -> FloatParam {
+    FloatParam::new(
+        name,
+        default,
+        FloatRange::Skewed {
+            min: MIN_FREQUENCY,
+            max: MAX_FREQUENCY,
+            factor: FloatRange::skew_factor(-2.0),
+        },
+    )
+    .with_unit(" Hz")
+    .with_value_to_string(formatters::v2s_f32_rounded(0))
+}
+
+/// The sweep's frequency at `t`, the fraction of the sweep time that has passed. The sweep moves
+/// linearly in octaves, bent by `curve`.
+pub fn sweep_frequency(start: f32, end: f32, curve: f32, t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0).powf(2.0f32.powf(-curve * 3.0));
+    start * (end / start).powf(t)
+}
+
+/// The position within the current sweep. Once the sweep is done the frequency glides back to
+/// `base_frequency`, after which the zap lets go until it's triggered again.
+#[derive(Default)]
+pub struct Zap {
+    /// The fraction of the sweep time that has passed, while a sweep is running.
+    position: Option<f32>,
+    /// The frequency on its way back to the parameter's, after a sweep.
+    release: Option<f32>,
+}
+
+impl Zap {
+    pub fn reset(&mut self) {
+        self.position = None;
+        self.release = None;
+    }
+
+    pub fn trigger(&mut self) {
+        self.position = Some(0.0);
+        self.release = None;
+    }
+
+    /// Advance the sweep by `num_samples` and return its frequency, or `None` if `base_frequency`
+    /// is in control.
+    pub fn next_frequency(
+        &mut self,
+        params: &ZapParams,
+        base_frequency: f32,
+        sample_rate: f32,
+        num_samples: usize,
+    ) -> Option<f32> {
+        if let Some(position) = self.position {
+            let frequency = sweep_frequency(
+                params.start_frequency.value(),
+                params.end_frequency.value(),
+                params.curve.value(),
+                position,
+            );
+
+            let sweep_samples = sample_rate * params.time.value() / 1000.0;
+            let position = position + num_samples as f32 / sweep_samples;
+            if position >= 1.0 {
+                self.position = None;
+                self.release = Some(params.end_frequency.value());
+            } else {
+                self.position = Some(position);
+            }
+
+            return Some(frequency);
+        }
+
+        let frequency = self.release?;
+        let weight = glide_weight(num_samples, sample_rate, RELEASE_GLIDE_MS);
+        let next = base_frequency * (frequency / base_frequency).powf(weight);
+        self.release = if (next / base_frequency).log2().abs() > RELEASE_DONE_OCTAVES {
+            Some(next)
+        } else {
+            None
+        };
+
+        Some(frequency)
+    }
+}
@@ -1,28 +1,139 @@
 use atomic_float::AtomicF32;
-use nih_plug::prelude::Editor;
+use nih_plug::prelude::{Editor, Param};
 use nih_plug::util;
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
 use vizia_plug::vizia::prelude::*;
+use vizia_plug::widgets::{
+    ParamButton, ParamButtonExt, ParamEvent, ParamSlider, ParamSliderExt, ParamSliderStyle,
+};
 use vizia_plug::{ViziaState, ViziaTheming, create_vizia_editor};
 
-use crate::DisperserParams;
+use crate::dsp::analyzer::AnalyzerTaps;
+use crate::dsp::meter::MeterLevels;
+use crate::dsp::multiband::MAX_BANDS;
+use crate::dsp::pitch::{LEARN_SPREAD_RATIO, PitchLearn};
 use crate::widgets::omg_peak_meter::OmgPeakMeter;
-use crate::widgets::params_knob::ParamKnob;
-use crate::widgets::waveform_view::WaveformView;
+use crate::widgets::params_knob::{ParamKnob, ParamKnobExt};
+use crate::widgets::response_view::ResponseView;
+use crate::widgets::spectrogram_view::{
+    SpectrogramColourMap, SpectrogramFftSize, SpectrogramRange, SpectrogramView, SpectrogramViewExt,
+};
+use crate::widgets::spectrum_view::{SpectrumAveraging, SpectrumView, SpectrumViewExt};
+use crate::widgets::sweep_view::SweepView;
+use crate::widgets::waveform_view::{TIME_BASES_MS, WaveformView, WaveformViewExt};
+use crate::{DisperserParams, ModulatedValues};
 
 // pub const NOTO_SANS: &str = "Noto Sans";
 
 #[derive(Lens)]
 struct Data {
     params: Arc<DisperserParams>,
-    pre_signal: Arc<AtomicF32>,
-    post_signal: Arc<AtomicF32>,
+    input_levels: Arc<MeterLevels>,
+    output_levels: Arc<MeterLevels>,
+    analyzer_taps: Arc<AnalyzerTaps>,
+    auto_gain: Arc<AtomicF32>,
+    modulated_values: Arc<ModulatedValues>,
+    pitch_learn: Arc<PitchLearn>,
+    sample_rate: Arc<AtomicF32>,
     is_show_info_panel: bool,
+    page: ControlPage,
+    /// The band shown on the multiband page.
+    band: usize,
+    display: AnalyzerDisplay,
+    spectrum_averaging: SpectrumAveraging,
+    spectrum_peak_hold: bool,
+    /// An index into [`TIME_BASES_MS`].
+    scope_time_base: usize,
+    scope_frozen: bool,
+    scope_overlay: bool,
+    spectrogram_fft_size: SpectrogramFftSize,
+    spectrogram_colour_map: SpectrogramColourMap,
+    spectrogram_range: SpectrogramRange,
+    /// Whether the spectrogram shows the output or the input.
+    spectrogram_output: bool,
+}
+
+/// What the left side of the spectrum panel shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum AnalyzerDisplay {
+    Spectrum,
+    Waveform,
+    Spectrogram,
+}
+
+impl AnalyzerDisplay {
+    const ALL: [AnalyzerDisplay; 3] = [
+        AnalyzerDisplay::Spectrum,
+        AnalyzerDisplay::Waveform,
+        AnalyzerDisplay::Spectrogram,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            AnalyzerDisplay::Spectrum => "SPECTRUM",
+            AnalyzerDisplay::Waveform => "SCOPE",
+            AnalyzerDisplay::Spectrogram => "SONOGRAM",
+        }
+    }
+}
+
+/// The groups of controls that can be shown in the control panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum ControlPage {
+    Main,
+    Gain,
+    Stereo,
+    Lfo,
+    Envelope,
+    Midi,
+    Pitch,
+    Zap,
+    Transient,
+    Feedback,
+    Spring,
+    Bands,
+    Quality,
+}
+
+impl ControlPage {
+    const ALL: [ControlPage; 13] = [
+        ControlPage::Main,
+        ControlPage::Gain,
+        ControlPage::Stereo,
+        ControlPage::Lfo,
+        ControlPage::Envelope,
+        ControlPage::Midi,
+        ControlPage::Pitch,
+        ControlPage::Zap,
+        ControlPage::Transient,
+        ControlPage::Feedback,
+        ControlPage::Spring,
+        ControlPage::Bands,
+        ControlPage::Quality,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ControlPage::Main => "MAIN",
+            ControlPage::Gain => "GAIN",
+            ControlPage::Stereo => "STEREO",
+            ControlPage::Lfo => "LFO",
+            ControlPage::Envelope => "ENV",
+            ControlPage::Midi => "MIDI",
+            ControlPage::Pitch => "PITCH",
+            ControlPage::Zap => "ZAP",
+            ControlPage::Transient => "TRANS",
+            ControlPage::Feedback => "FDBK",
+            ControlPage::Spring => "SPRING",
+            ControlPage::Bands => "BANDS",
+            ControlPage::Quality => "QUALITY",
+        }
+    }
 }
 
 impl Model for Data {
-    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
         event.map(|main_view_event, _meta| match main_view_event {
             MainViewEvent::ToggleInfoPanel => {
                 self.is_show_info_panel = !self.is_show_info_panel;
@@ -32,6 +143,65 @@ impl Model for Data {
                     println!("Failed to open URL: {}", url);
                 }
             }
+            MainViewEvent::SetPage(page) => {
+                self.page = *page;
+            }
+            MainViewEvent::SetBand(band) => {
+                self.band = *band;
+            }
+            MainViewEvent::SetDisplay(display) => {
+                self.display = *display;
+            }
+            MainViewEvent::CycleSpectrumAveraging => {
+                self.spectrum_averaging = self.spectrum_averaging.next();
+            }
+            MainViewEvent::ToggleSpectrumPeakHold => {
+                self.spectrum_peak_hold = !self.spectrum_peak_hold;
+            }
+            MainViewEvent::ZoomScope(steps) => {
+                self.scope_time_base = self
+                    .scope_time_base
+                    .saturating_add_signed(*steps)
+                    .min(TIME_BASES_MS.len() - 1);
+            }
+            MainViewEvent::ToggleScopeFreeze => {
+                self.scope_frozen = !self.scope_frozen;
+            }
+            MainViewEvent::ToggleScopeOverlay => {
+                self.scope_overlay = !self.scope_overlay;
+            }
+            MainViewEvent::CycleSpectrogramFftSize => {
+                self.spectrogram_fft_size = self.spectrogram_fft_size.next();
+            }
+            MainViewEvent::CycleSpectrogramColourMap => {
+                self.spectrogram_colour_map = self.spectrogram_colour_map.next();
+            }
+            MainViewEvent::CycleSpectrogramRange => {
+                self.spectrogram_range = self.spectrogram_range.next();
+            }
+            MainViewEvent::ToggleSpectrogramSource => {
+                self.spectrogram_output = !self.spectrogram_output;
+            }
+            MainViewEvent::LearnPitch => {
+                self.pitch_learn.request();
+            }
+            MainViewEvent::ResetLoudness => {
+                self.output_levels.request_reset();
+            }
+            MainViewEvent::ApplyLearnedPitch => {
+                // The applied generation lives with the plugin, so a learn that finished while
+                // the editor was closed still gets applied when it opens again
+                let generation = self.pitch_learn.generation();
+                if generation != self.pitch_learn.applied_generation() {
+                    self.pitch_learn.set_applied_generation(generation);
+
+                    let frequency = self.pitch_learn.frequency();
+                    set_parameter(cx, &self.params.frequency, frequency);
+                    if self.params.pitch.learn_spread.value() {
+                        set_parameter(cx, &self.params.spread, frequency * LEARN_SPREAD_RATIO);
+                    }
+                }
+            }
         });
     }
 }
@@ -39,16 +209,47 @@ impl Model for Data {
 pub enum MainViewEvent {
     ToggleInfoPanel,
     OpenUrl(String),
+    SetPage(ControlPage),
+    SetBand(usize),
+    SetDisplay(AnalyzerDisplay),
+    CycleSpectrumAveraging,
+    ToggleSpectrumPeakHold,
+    /// Zoom the scope in (negative) or out (positive) by this many time bases.
+    ZoomScope(isize),
+    ToggleScopeFreeze,
+    ToggleScopeOverlay,
+    CycleSpectrogramFftSize,
+    CycleSpectrogramColourMap,
+    CycleSpectrogramRange,
+    /// Switch the spectrogram between the input and the output.
+    ToggleSpectrogramSource,
+    LearnPitch,
+    ResetLoudness,
+    /// Sent whenever the audio thread may have finished learning a pitch.
+    ApplyLearnedPitch,
+}
+
+/// Set a parameter from the editor as a single gesture, so the host records it properly.
+fn set_parameter<P: Param>(cx: &mut EventContext, param: &P, value: P::Plain) {
+    cx.emit(ParamEvent::BeginSetParameter(param).upcast());
+    cx.emit(ParamEvent::SetParameter(param, value).upcast());
+    cx.emit(ParamEvent::EndSetParameter(param).upcast());
 }
 
 pub(crate) fn default_state() -> Arc<ViziaState> {
     ViziaState::new(|| (800, 500))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn create(
     params: Arc<DisperserParams>,
-    pre_signal: Arc<AtomicF32>,
-    post_signal: Arc<AtomicF32>,
+    input_levels: Arc<MeterLevels>,
+    output_levels: Arc<MeterLevels>,
+    analyzer_taps: Arc<AnalyzerTaps>,
+    auto_gain: Arc<AtomicF32>,
+    modulated_values: Arc<ModulatedValues>,
+    pitch_learn: Arc<PitchLearn>,
+    sample_rate: Arc<AtomicF32>,
     editor_state: Arc<ViziaState>,
 ) -> Option<Box<dyn Editor>> {
     create_vizia_editor(editor_state, ViziaTheming::Custom, move |cx, _| {
@@ -61,12 +262,36 @@ pub(crate) fn create(
 
         Data {
             params: params.clone(),
-            pre_signal: pre_signal.clone(),
-            post_signal: post_signal.clone(),
+            input_levels: input_levels.clone(),
+            output_levels: output_levels.clone(),
+            analyzer_taps: analyzer_taps.clone(),
+            auto_gain: auto_gain.clone(),
+            modulated_values: modulated_values.clone(),
+            pitch_learn: pitch_learn.clone(),
+            sample_rate: sample_rate.clone(),
             is_show_info_panel: false,
+            page: ControlPage::Main,
+            band: 0,
+            display: AnalyzerDisplay::Spectrum,
+            spectrum_averaging: SpectrumAveraging::Fast,
+            spectrum_peak_hold: false,
+            scope_time_base: 3,
+            scope_frozen: false,
+            scope_overlay: true,
+            spectrogram_fft_size: SpectrogramFftSize::Fft2048,
+            spectrogram_colour_map: SpectrogramColourMap::Green,
+            spectrogram_range: SpectrogramRange::Db80,
+            spectrogram_output: true,
         }
         .build(cx);
 
+        // Learning finishes on the audio thread, the result gets applied to the parameters here
+        Binding::new(
+            cx,
+            Data::pitch_learn.map(|pitch_learn| pitch_learn.generation()),
+            |cx, _| cx.emit(MainViewEvent::ApplyLearnedPitch),
+        );
+
         VStack::new(cx, |cx| {
             VStack::new(cx, |cx| {
                 HStack::new(cx, |cx| {
@@ -75,13 +300,50 @@ pub(crate) fn create(
                     HStack::new(cx, |_| {}).width(Stretch(1.0));
 
                     HStack::new(cx, |cx| {
-                        Label::new(cx, "PROCESSING").class("top-bar-text");
+                        Binding::new(
+                            cx,
+                            Data::params.map(|params| params.auto_gain.value()),
+                            |cx, auto_gain_enabled| {
+                                if auto_gain_enabled.get(cx) {
+                                    Label::new(
+                                        cx,
+                                        Data::auto_gain.map(|auto_gain| {
+                                            format!(
+                                                "AUTO {:+.1} dB",
+                                                util::gain_to_db(auto_gain.load(Ordering::Relaxed))
+                                            )
+                                        }),
+                                    )
+                                    .class("top-bar-text");
+                                }
+                            },
+                        );
+
+                        // Clicking the loudness readout starts the integrated measurement over
+                        Button::new(cx, |cx| {
+                            Label::new(
+                                cx,
+                                Data::output_levels.map(|levels| {
+                                    format!(
+                                        "S {} I {} LUFS",
+                                        format_lufs(levels.short_term_lufs()),
+                                        format_lufs(levels.integrated_lufs())
+                                    )
+                                }),
+                            )
+                            .class("top-bar-text")
+                        })
+                        .on_press(|ex| ex.emit(MainViewEvent::ResetLoudness))
+                        .class("loudness-btn");
+
+                        Label::new(cx, "IN").class("top-bar-text");
+                        OmgPeakMeter::new(cx, Data::input_levels, Some(Duration::from_millis(600)))
+                            .class("peak-meter");
 
+                        Label::new(cx, "OUT").class("top-bar-text");
                         OmgPeakMeter::new(
                             cx,
-                            Data::post_signal.map(|post_signal| {
-                                util::gain_to_db(post_signal.load(Ordering::Relaxed))
-                            }),
+                            Data::output_levels,
                             Some(Duration::from_millis(600)),
                         )
                         .class("peak-meter");
@@ -96,14 +358,36 @@ pub(crate) fn create(
                 })
                 .class("top-bar");
 
-                VStack::new(cx, |cx| {
-                    WaveformView::new(
+                HStack::new(cx, |cx| {
+                    VStack::new(cx, analyzer).class("analyzer");
+
+                    // Shows the cascade as it currently sounds, modulation included. Dragging
+                    // it sets the unmodulated parameters.
+                    let (frequency_params, spread_params, amount_params, oversampling_params) = (
+                        params.clone(),
+                        params.clone(),
+                        params.clone(),
+                        params.clone(),
+                    );
+                    ResponseView::new(
                         cx,
-                        Data::pre_signal.map(|pre_signal| pre_signal.load(Ordering::Relaxed)),
-                        Data::post_signal.map(|post_signal| post_signal.load(Ordering::Relaxed)),
-                        512,
+                        Data::params,
+                        Data::modulated_values.map(move |values| {
+                            modulated_plain(&frequency_params.frequency, &values.frequency)
+                        }),
+                        Data::modulated_values.map(move |values| {
+                            modulated_plain(&spread_params.spread, &values.spread)
+                        }),
+                        Data::modulated_values.map(move |values| {
+                            modulated_plain(&amount_params.amount, &values.amount) as usize
+                        }),
+                        Data::sample_rate.map(move |sample_rate| {
+                            // The cascades run at the oversampled rate
+                            let stages = oversampling_params.oversampling.value().stages();
+                            sample_rate.load(Ordering::Relaxed) * (1 << stages) as f32
+                        }),
                     )
-                    .class("waveform-view");
+                    .class("response-view");
                 })
                 .height(Stretch(1.0));
             })
@@ -130,34 +414,42 @@ pub(crate) fn create(
                     .padding_left(Pixels(48.0))
                     .alignment(Alignment::Left);
 
-                    HStack::new(cx, |_| {}).width(Stretch(1.0));
-
-                    HStack::new(cx, |cx| {
-                        VStack::new(cx, |cx| {
-                            ParamKnob::new(cx, Data::params, |params| &params.amount, true)
-                                .class("knob");
-                            Label::new(cx, "AMOUNT").class("params-label");
-                        })
-                        .class("knob-cont");
+                    VStack::new(cx, |cx| {
+                        for row in ControlPage::ALL.chunks(4) {
+                            HStack::new(cx, |cx| {
+                                for &page in row {
+                                    Button::new(cx, move |cx| Label::new(cx, page.name()))
+                                        .on_press(move |cx| cx.emit(MainViewEvent::SetPage(page)))
+                                        .checked(Data::page.map(move |current| *current == page))
+                                        .class("page-btn");
+                                }
+                            })
+                            .class("page-tab-row");
+                        }
+                    })
+                    .class("page-tabs");
 
-                        VStack::new(cx, |cx| {
-                            ParamKnob::new(cx, Data::params, |params| &params.spread, true)
-                                .class("knob");
-                            Label::new(cx, "SPREAD").class("params-label");
-                        })
-                        // genshin impact is the worst game in the world
-                        .class("knob-cont");
+                    HStack::new(cx, |_| {}).width(Stretch(1.0));
 
-                        VStack::new(cx, |cx| {
-                            ParamKnob::new(cx, Data::params, |params| &params.frequency, true)
-                                .class("knob");
-                            Label::new(cx, "FREQUENCY").class("params-label");
+                    Binding::new(cx, Data::page, |cx, page| {
+                        let page = page.get(cx);
+                        HStack::new(cx, |cx| match page {
+                            ControlPage::Main => main_page(cx),
+                            ControlPage::Gain => gain_page(cx),
+                            ControlPage::Stereo => stereo_page(cx),
+                            ControlPage::Lfo => lfo_page(cx),
+                            ControlPage::Envelope => envelope_page(cx),
+                            ControlPage::Midi => midi_page(cx),
+                            ControlPage::Pitch => pitch_page(cx),
+                            ControlPage::Zap => zap_page(cx),
+                            ControlPage::Transient => transient_page(cx),
+                            ControlPage::Feedback => feedback_page(cx),
+                            ControlPage::Spring => spring_page(cx),
+                            ControlPage::Bands => bands_page(cx),
+                            ControlPage::Quality => quality_page(cx),
                         })
-                        .class("knob-cont");
-                    })
-                    .padding_right(Pixels(48.0))
-                    .alignment(Alignment::Right)
-                    .gap(Pixels(24.0));
+                        .class("page");
+                    });
                 })
                 .height(Stretch(1.0));
             })
@@ -228,3 +520,414 @@ pub(crate) fn create(
         // .alignment(Alignment::TopCenter);
     })
 }
+
+/// The input/output analyzers, with their options above them.
+fn analyzer(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        for display in AnalyzerDisplay::ALL {
+            Button::new(cx, move |cx| Label::new(cx, display.name()))
+                .on_press(move |cx| cx.emit(MainViewEvent::SetDisplay(display)))
+                .checked(Data::display.map(move |current| *current == display))
+                .class("display-btn");
+        }
+
+        HStack::new(cx, |_| {}).width(Stretch(1.0));
+
+        Binding::new(cx, Data::display, |cx, display| match display.get(cx) {
+            AnalyzerDisplay::Spectrum => {
+                Button::new(cx, |cx| {
+                    Label::new(
+                        cx,
+                        Data::spectrum_averaging.map(|averaging| averaging.label()),
+                    )
+                })
+                .on_press(|cx| cx.emit(MainViewEvent::CycleSpectrumAveraging))
+                .class("display-btn");
+                Button::new(cx, |cx| Label::new(cx, "HOLD"))
+                    .on_press(|cx| cx.emit(MainViewEvent::ToggleSpectrumPeakHold))
+                    .checked(Data::spectrum_peak_hold)
+                    .class("display-btn");
+            }
+            AnalyzerDisplay::Waveform => {
+                Button::new(cx, |cx| Label::new(cx, "-"))
+                    .on_press(|cx| cx.emit(MainViewEvent::ZoomScope(-1)))
+                    .class("display-btn");
+                Label::new(
+                    cx,
+                    Data::scope_time_base.map(|idx| format!("{} ms", TIME_BASES_MS[*idx])),
+                )
+                .class("display-label");
+                Button::new(cx, |cx| Label::new(cx, "+"))
+                    .on_press(|cx| cx.emit(MainViewEvent::ZoomScope(1)))
+                    .class("display-btn");
+                Button::new(cx, |cx| Label::new(cx, "DRY"))
+                    .on_press(|cx| cx.emit(MainViewEvent::ToggleScopeOverlay))
+                    .checked(Data::scope_overlay)
+                    .class("display-btn");
+                Button::new(cx, |cx| Label::new(cx, "FREEZE"))
+                    .on_press(|cx| cx.emit(MainViewEvent::ToggleScopeFreeze))
+                    .checked(Data::scope_frozen)
+                    .class("display-btn");
+            }
+            AnalyzerDisplay::Spectrogram => {
+                Button::new(cx, |cx| {
+                    Label::new(
+                        cx,
+                        Data::spectrogram_output.map(|output| if *output { "OUT" } else { "IN" }),
+                    )
+                })
+                .on_press(|cx| cx.emit(MainViewEvent::ToggleSpectrogramSource))
+                .class("display-btn");
+                Button::new(cx, |cx| {
+                    Label::new(cx, Data::spectrogram_fft_size.map(|size| size.label()))
+                })
+                .on_press(|cx| cx.emit(MainViewEvent::CycleSpectrogramFftSize))
+                .class("display-btn");
+                Button::new(cx, |cx| {
+                    Label::new(cx, Data::spectrogram_range.map(|range| range.label()))
+                })
+                .on_press(|cx| cx.emit(MainViewEvent::CycleSpectrogramRange))
+                .class("display-btn");
+                Button::new(cx, |cx| {
+                    Label::new(cx, Data::spectrogram_colour_map.map(|map| map.label()))
+                })
+                .on_press(|cx| cx.emit(MainViewEvent::CycleSpectrogramColourMap))
+                .class("display-btn");
+            }
+        });
+    })
+    .class("display-row");
+
+    Binding::new(cx, Data::display, |cx, display| match display.get(cx) {
+        AnalyzerDisplay::Spectrum => {
+            SpectrumView::new(
+                cx,
+                Data::analyzer_taps,
+                Data::analyzer_taps.map(|taps| taps.sample_rate()),
+            )
+            .averaging(Data::spectrum_averaging)
+            .peak_hold(Data::spectrum_peak_hold)
+            .class("spectrum-view");
+        }
+        AnalyzerDisplay::Waveform => {
+            WaveformView::new(
+                cx,
+                Data::analyzer_taps,
+                Data::analyzer_taps.map(|taps| taps.sample_rate()),
+            )
+            .time_base(Data::scope_time_base.map(|idx| TIME_BASES_MS[*idx]))
+            .frozen(Data::scope_frozen)
+            .overlay(Data::scope_overlay)
+            .class("waveform-view");
+        }
+        AnalyzerDisplay::Spectrogram => {
+            SpectrogramView::new(
+                cx,
+                Data::analyzer_taps,
+                Data::analyzer_taps.map(|taps| taps.sample_rate()),
+            )
+            .fft_size(Data::spectrogram_fft_size)
+            .colour_map(Data::spectrogram_colour_map)
+            .range(Data::spectrogram_range)
+            .show_output(Data::spectrogram_output)
+            .class("spectrogram-view");
+        }
+    });
+}
+
+/// The parameter's modulated value if the audio thread reported one, its own value otherwise.
+fn modulated_plain<P: Param>(param: &P, modulated_value: &AtomicF32) -> P::Plain {
+    let normalized = modulated_value.load(Ordering::Relaxed);
+    if normalized >= 0.0 {
+        param.preview_plain(normalized)
+    } else {
+        param.modulated_plain_value()
+    }
+}
+
+/// Loudness readouts below the meters' range are shown as `-inf`.
+fn format_lufs(lufs: f32) -> String {
+    if lufs > util::MINUS_INFINITY_DB {
+        format!("{lufs:.1}")
+    } else {
+        String::from("-inf")
+    }
+}
+
+/// A [`ParamKnob`] with a label underneath it.
+fn knob<P, F>(cx: &mut Context, label: &'static str, small: bool, params_to_param: F)
+where
+    P: Param + 'static,
+    F: Fn(&Arc<DisperserParams>) -> &P + Copy + 'static,
+{
+    VStack::new(cx, |cx| {
+        ParamKnob::new(cx, Data::params, params_to_param, true).class(if small {
+            "knob-small"
+        } else {
+            "knob"
+        });
+        Label::new(cx, label).class("params-label");
+    })
+    .class("knob-cont");
+}
+
+/// A big [`knob()`] that also shows the parameter's modulated value on its ring.
+fn modulated_knob<P, F>(
+    cx: &mut Context,
+    label: &'static str,
+    params_to_param: F,
+    modulated_value: fn(&ModulatedValues) -> &AtomicF32,
+) where
+    P: Param + 'static,
+    F: Fn(&Arc<DisperserParams>) -> &P + Copy + 'static,
+{
+    VStack::new(cx, |cx| {
+        ParamKnob::new(cx, Data::params, params_to_param, true)
+            .modulated_value(
+                Data::modulated_values
+                    .map(move |values| modulated_value(values).load(Ordering::Relaxed)),
+            )
+            .class("knob");
+        Label::new(cx, label).class("params-label");
+    })
+    .class("knob-cont");
+}
+
+fn main_page(cx: &mut Context) {
+    knob(cx, "MIX", false, |params| &params.mix);
+    modulated_knob(
+        cx,
+        "AMOUNT",
+        |params| &params.amount,
+        |values| &values.amount,
+    );
+    // genshin impact is the worst game in the world
+    modulated_knob(
+        cx,
+        "SPREAD",
+        |params| &params.spread,
+        |values| &values.spread,
+    );
+    modulated_knob(
+        cx,
+        "FREQUENCY",
+        |params| &params.frequency,
+        |values| &values.frequency,
+    );
+}
+
+fn gain_page(cx: &mut Context) {
+    knob(cx, "IN", false, |params| &params.input_gain);
+    knob(cx, "OUT", false, |params| &params.output_gain);
+
+    ParamButton::new(cx, Data::params, |params| &params.auto_gain)
+        .with_label("AUTO GAIN")
+        .class("toggle-btn");
+}
+
+fn stereo_page(cx: &mut Context) {
+    selector(cx, "STEREO MODE", |params| &params.stereo_mode);
+
+    knob(cx, "SIDE AMOUNT", false, |params| &params.side_amount);
+
+    VStack::new(cx, |cx| {
+        knob(cx, "OFFSET", true, |params| &params.stereo_offset);
+        ParamButton::new(cx, Data::params, |params| &params.stereo_link)
+            .with_label("L/R LINK")
+            .class("toggle-btn");
+    })
+    .class("knob-cont");
+}
+
+/// A stepped [`ParamSlider`] for enum parameters, with a label underneath it.
+fn selector<P, F>(cx: &mut Context, label: &'static str, params_to_param: F)
+where
+    P: Param + 'static,
+    F: Fn(&Arc<DisperserParams>) -> &P + Copy + 'static,
+{
+    VStack::new(cx, |cx| {
+        ParamSlider::new(cx, Data::params, params_to_param)
+            .set_style(ParamSliderStyle::CurrentStepLabeled { even: true })
+            .class("mode-slider");
+        Label::new(cx, label).class("params-label");
+    })
+    .class("knob-cont");
+}
+
+fn lfo_page(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        selector(cx, "SHAPE", |params| &params.lfo.shape);
+        selector(cx, "TARGET", |params| &params.lfo.target);
+    })
+    .class("selector-column");
+
+    VStack::new(cx, |cx| {
+        selector(cx, "DIVISION", |params| &params.lfo.division);
+        ParamButton::new(cx, Data::params, |params| &params.lfo.sync)
+            .with_label("TEMPO SYNC")
+            .class("toggle-btn");
+    })
+    .class("selector-column");
+
+    knob(cx, "RATE", false, |params| &params.lfo.rate);
+    knob(cx, "DEPTH", false, |params| &params.lfo.depth);
+}
+
+fn envelope_page(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        selector(cx, "SOURCE", |params| &params.envelope.source);
+        selector(cx, "TARGET", |params| &params.envelope.target);
+    })
+    .class("selector-column");
+
+    knob(cx, "ATTACK", false, |params| &params.envelope.attack);
+    knob(cx, "RELEASE", false, |params| &params.envelope.release);
+    knob(cx, "THRESHOLD", false, |params| &params.envelope.threshold);
+    knob(cx, "DEPTH", false, |params| &params.envelope.depth);
+}
+
+fn midi_page(cx: &mut Context) {
+    ParamButton::new(cx, Data::params, |params| &params.keytrack.enabled)
+        .with_label("KEYTRACK")
+        .class("toggle-btn");
+
+    knob(cx, "OCTAVE", false, |params| &params.keytrack.octave);
+    knob(cx, "SEMITONE", false, |params| &params.keytrack.semitone);
+    knob(cx, "GLIDE", false, |params| &params.keytrack.glide);
+}
+
+fn pitch_page(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        Button::new(cx, |cx| {
+            Label::new(
+                cx,
+                Data::pitch_learn.map(|pitch_learn| {
+                    if pitch_learn.is_learning() {
+                        "LISTENING..."
+                    } else {
+                        "LEARN"
+                    }
+                }),
+            )
+        })
+        .on_press(|cx| cx.emit(MainViewEvent::LearnPitch))
+        .checked(Data::pitch_learn.map(|pitch_learn| pitch_learn.is_learning()))
+        .class("toggle-btn");
+        ParamButton::new(cx, Data::params, |params| &params.pitch.learn_spread)
+            .with_label("LEARN SPREAD")
+            .class("toggle-btn");
+    })
+    .class("selector-column");
+
+    VStack::new(cx, |cx| {
+        ParamButton::new(cx, Data::params, |params| &params.pitch.follow)
+            .with_label("FOLLOW")
+            .class("toggle-btn");
+    })
+    .class("selector-column");
+
+    knob(cx, "GLIDE", false, |params| &params.pitch.glide);
+}
+
+fn zap_page(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        selector(cx, "TRIGGER", |params| &params.zap.trigger);
+        SweepView::new(
+            cx,
+            Data::params.map(|params| params.zap.start_frequency.value()),
+            Data::params.map(|params| params.zap.end_frequency.value()),
+            Data::params.map(|params| params.zap.curve.value()),
+        )
+        .class("sweep-view");
+    })
+    .class("selector-column");
+
+    knob(cx, "START", true, |params| &params.zap.start_frequency);
+    knob(cx, "END", true, |params| &params.zap.end_frequency);
+    knob(cx, "TIME", true, |params| &params.zap.time);
+    knob(cx, "CURVE", true, |params| &params.zap.curve);
+    knob(cx, "THRESH", true, |params| &params.zap.transient_threshold);
+}
+
+fn transient_page(cx: &mut Context) {
+    ParamButton::new(cx, Data::params, |params| &params.transient.preserve)
+        .with_label("PRESERVE")
+        .class("toggle-btn");
+
+    knob(cx, "SENSITIVITY", false, |params| {
+        &params.transient.sensitivity
+    });
+    knob(cx, "ATTACK", false, |params| &params.transient.amount);
+}
+
+fn feedback_page(cx: &mut Context) {
+    knob(cx, "FEEDBACK", false, |params| &params.feedback.gain);
+    knob(cx, "DAMPING", false, |params| &params.feedback.damping);
+}
+
+fn spring_page(cx: &mut Context) {
+    ParamButton::new(cx, Data::params, |params| &params.spring.enabled)
+        .with_label("SPRING")
+        .class("toggle-btn");
+
+    knob(cx, "TENSION", false, |params| &params.spring.tension);
+    knob(cx, "DECAY", false, |params| &params.spring.decay);
+    knob(cx, "LENGTH", true, |params| &params.spring.length);
+    knob(cx, "SPRINGS", true, |params| &params.spring.springs);
+}
+
+fn bands_page(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        knob(cx, "BANDS", true, |params| &params.multiband.bands);
+        HStack::new(cx, |cx| {
+            for band in 0..MAX_BANDS {
+                Button::new(cx, move |cx| Label::new(cx, (band + 1).to_string()))
+                    .on_press(move |cx| cx.emit(MainViewEvent::SetBand(band)))
+                    .checked(Data::band.map(move |current| *current == band))
+                    .class("page-btn")
+                    .class("band-btn");
+            }
+        })
+        .class("page-tab-row");
+    })
+    .class("selector-column");
+
+    knob(cx, "LOW X", true, |params| &params.multiband.crossover_low);
+    knob(cx, "MID X", true, |params| &params.multiband.crossover_mid);
+    knob(cx, "HIGH X", true, |params| {
+        &params.multiband.crossover_high
+    });
+
+    Binding::new(cx, Data::band, |cx, band| {
+        let band = band.get(cx);
+
+        knob(cx, "FREQUENCY", true, move |params| {
+            &params.multiband.band[band].frequency
+        });
+        knob(cx, "SPREAD", true, move |params| {
+            &params.multiband.band[band].spread
+        });
+        knob(cx, "AMOUNT", true, move |params| {
+            &params.multiband.band[band].amount
+        });
+
+        VStack::new(cx, |cx| {
+            ParamButton::new(cx, Data::params, move |params| {
+                &params.multiband.band[band].solo
+            })
+            .with_label("SOLO")
+            .class("toggle-btn");
+            ParamButton::new(cx, Data::params, move |params| {
+                &params.multiband.band[band].mute
+            })
+            .with_label("MUTE")
+            .class("toggle-btn");
+        })
+        .class("selector-column");
+    });
+}
+
+fn quality_page(cx: &mut Context) {
+    selector(cx, "OVERSAMPLING", |params| &params.oversampling);
+    selector(cx, "FILTER", |params| &params.oversampling_filter);
+}
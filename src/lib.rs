@@ -3,25 +3,227 @@ use nih_plug::prelude::*;
 use std::sync::Arc;
 use vizia_plug::ViziaState;
 
-use i_am_dsp::{
-    Effect, ProcessContext as DspContext, ProcessInfos, prelude::Disperser,
-    real_time_demo::SimpleContext,
+use i_am_dsp::{ProcessContext as DspContext, ProcessInfos, real_time_demo::SimpleContext};
+
+use crate::dsp::analyzer::{AnalyzerTaps, TapDecimator};
+use crate::dsp::crossfading_disperser::CrossfadingDisperser;
+use crate::dsp::envelope::{
+    ENVELOPE_RANGE_OCTAVES, EnvelopeFollower, EnvelopeParams, EnvelopeSource, ModulationTarget,
+};
+use crate::dsp::feedback::{FeedbackLoop, FeedbackParams, TAIL_SILENCE_DB};
+use crate::dsp::keytrack::{KeytrackParams, Keytracker, note_to_freq};
+use crate::dsp::lfo::{LFO_RANGE_OCTAVES, Lfo, LfoParams, LfoTarget};
+use crate::dsp::meter::{Meter, MeterLevels};
+use crate::dsp::multiband::{BandOffsets, Multiband, MultibandParams};
+use crate::dsp::oversampling::{
+    LatencyDelay, MAX_FACTOR, MAX_STAGES, Oversampler, Oversampling, OversamplingFilter,
+    latency_samples,
 };
+use crate::dsp::pitch::{PitchDetector, PitchLearn, PitchLearner, PitchParams};
+use crate::dsp::spring::{SpringParams, SpringTank};
+use crate::dsp::transient::{TransientDetector, TransientParams};
+use crate::dsp::zap::{Zap, ZapParams, ZapTrigger};
 
+mod dsp;
 mod editor;
+#[cfg(test)]
+mod tests;
 mod widgets;
 
-const PEAK_METER_DECAY_MS: f64 = 150.0;
+/// The largest supported channel count, 7.1 surround.
+const MAX_CHANNELS: usize = 8;
+
+const MIN_FREQUENCY: f32 = 20.0;
+const MAX_FREQUENCY: f32 = 20000.0;
+const MAX_AMOUNT: i32 = 100;
+const MIN_SPREAD: f32 = 0.1;
+const MAX_SPREAD: f32 = 2000.0;
+
+/// Every layout has a sidechain input that can drive the envelope follower.
+const SIDECHAIN_PORT_NAMES: PortNames = PortNames {
+    aux_inputs: &["Sidechain"],
+    ..PortNames::const_default()
+};
+
+/// The window used to estimate the short-term RMS of the pre and post signals for auto gain.
+const AUTO_GAIN_RMS_MS: f64 = 300.0;
+/// How fast the auto gain compensation follows the RMS ratio, also avoids jumps when toggling it.
+const AUTO_GAIN_SMOOTHING_MS: f64 = 50.0;
+/// The auto gain compensation never goes beyond this, so silence can't pump the output up.
+const AUTO_GAIN_MAX_DB: f32 = 24.0;
+
+/// The longest tail reported to the host. The feedback loop's estimate grows without bound for
+/// tiny spreads and feedback close to its maximum.
+const MAX_TAIL_SECONDS: f32 = 10.0;
 
 pub struct DisperserPlugin {
     params: Arc<DisperserParams>,
 
-    disperser: Disperser<2>,
+    /// One set of cascades per oversampling factor, indexed by the number of oversampling stages.
+    cascades: Vec<Cascades>,
+    oversampler: Oversampler,
+    /// Keep the dry signal and the transients lined up with the oversampled cascades.
+    dry_delay: LatencyDelay,
+    transient_delay: LatencyDelay,
+    /// The number of oversampling stages used for the current block.
+    oversampling_stages: usize,
+    /// The latency last reported to the host.
+    latency: u32,
+    /// How much longer the cascades keep ringing after the last block with any input, in samples.
+    ringing_samples: u32,
     sample_rate: f32,
+    /// The sample rate again, for drawing the cascade's response in the editor.
+    shared_sample_rate: Arc<AtomicF32>,
+    /// Created up front so `process()` doesn't need to allocate one for every block.
+    dsp_ctx: Box<dyn DspContext>,
+    /// Whether the mono input needs to be copied to the second output channel.
+    mono_to_stereo: bool,
+    stereo_mode: StereoMode,
 
-    peak_meter_decay_weight: f32,
-    pre_signal: Arc<AtomicF32>,
-    post_signal: Arc<AtomicF32>,
+    /// The levels going into the cascades and coming out of the plugin.
+    input_meter: Meter,
+    output_meter: Meter,
+    input_levels: Arc<MeterLevels>,
+    output_levels: Arc<MeterLevels>,
+    analyzer_taps: Arc<AnalyzerTaps>,
+    tap_decimator: TapDecimator,
+    /// Whether the editor is open for the current block. The analyzers and the modulated values
+    /// shown on the knobs are only updated while it is.
+    editor_open: bool,
+
+    auto_gain_rms_weight: f32,
+    auto_gain_smoothing_weight: f32,
+    pre_mean_square: f32,
+    post_mean_square: f32,
+    auto_gain: Arc<AtomicF32>,
+
+    envelope_follower: EnvelopeFollower,
+    keytracker: Keytracker,
+    pitch_detector: PitchDetector,
+    pitch_learner: PitchLearner,
+    pitch_learn: Arc<PitchLearn>,
+    /// The last pitch the detector found, which pitch follow glides towards.
+    pitch_target: Option<f32>,
+    followed_frequency: Option<f32>,
+    transient_detector: TransientDetector,
+    zap: Zap,
+    lfo: Lfo,
+    /// How far the LFO moves per sample in the current block, in cycles.
+    lfo_phase_delta: f32,
+    modulated_values: Arc<ModulatedValues>,
+}
+
+/// Everything that runs at the oversampled rate. The cascades can only be set up for a single
+/// sample rate, so there's one of these for every oversampling factor and switching between them
+/// doesn't need to allocate.
+struct Cascades {
+    sample_rate: f32,
+    /// One cascade per main output channel, so any channel layout can be processed.
+    dispersers: Vec<CrossfadingDisperser<1>>,
+    feedback_loop: FeedbackLoop,
+    multiband: Multiband,
+    spring_tank: SpringTank,
+}
+
+impl Cascades {
+    fn new(sample_rate: f32, channels: usize) -> Self {
+        Self {
+            sample_rate,
+            dispersers: (0..channels)
+                .map(|_| CrossfadingDisperser::<1>::new(sample_rate))
+                .collect(),
+            feedback_loop: FeedbackLoop::default(),
+            multiband: Multiband::new(sample_rate, channels),
+            spring_tank: SpringTank::new(sample_rate, channels),
+        }
+    }
+
+    fn reset(&mut self) {
+        for disperser in &mut self.dispersers {
+            disperser.reset();
+        }
+        self.feedback_loop.reset();
+        self.multiband.reset();
+        self.spring_tank.reset();
+    }
+}
+
+/// How far the modulation sources move the parameters for one coefficient update.
+#[derive(Default)]
+struct Modulation {
+    frequency_octaves: f32,
+    spread_octaves: f32,
+    /// Added to the number of stages.
+    amount: f32,
+}
+
+impl Modulation {
+    /// Add a bipolar `[-1, 1]` modulation signal to one of the targets at its full range.
+    fn add(&mut self, target: ModulationTarget, value: f32) {
+        match target {
+            ModulationTarget::Frequency => self.frequency_octaves += value * ENVELOPE_RANGE_OCTAVES,
+            ModulationTarget::Spread => self.spread_octaves += value * ENVELOPE_RANGE_OCTAVES,
+            ModulationTarget::Amount => self.amount += value * MAX_AMOUNT as f32,
+        }
+    }
+}
+
+/// The normalized parameter values after modulation, so the editor can show them next to the
+/// values set by the user. A negative value means the parameter isn't being modulated.
+pub(crate) struct ModulatedValues {
+    pub frequency: AtomicF32,
+    pub spread: AtomicF32,
+    pub amount: AtomicF32,
+}
+
+impl Default for ModulatedValues {
+    fn default() -> Self {
+        Self {
+            frequency: AtomicF32::new(-1.0),
+            spread: AtomicF32::new(-1.0),
+            amount: AtomicF32::new(-1.0),
+        }
+    }
+}
+
+/// How the disperser reacts when the number of stages changes.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmountTransition {
+    /// Add or drop stages immediately. Cheapest, but clicks.
+    #[id = "jump"]
+    Jump,
+    /// Fade over to a second cascade with the new number of stages.
+    #[id = "crossfade"]
+    Crossfade,
+}
+
+/// Which part of a stereo signal gets dispersed. Only used with two channels.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StereoMode {
+    #[id = "lr"]
+    #[name = "L/R"]
+    LeftRight,
+    #[id = "mid"]
+    #[name = "Mid"]
+    Mid,
+    #[id = "side"]
+    #[name = "Side"]
+    Side,
+    /// Both mid and side, with the side using its own amount.
+    #[id = "mid_side"]
+    #[name = "Mid+Side"]
+    MidSide,
+}
+
+impl StereoMode {
+    /// Whether the cascade for the given (encoded) channel should run.
+    fn processes_channel(self, channel: usize) -> bool {
+        match self {
+            StereoMode::LeftRight | StereoMode::MidSide => true,
+            StereoMode::Mid => channel == 0,
+            StereoMode::Side => channel == 1,
+        }
+    }
 }
 
 #[derive(Params)]
@@ -37,18 +239,120 @@ struct DisperserParams {
 
     #[id = "amount"]
     pub amount: IntParam,
+
+    #[id = "amount_transition"]
+    pub amount_transition: EnumParam<AmountTransition>,
+
+    #[id = "stereo_mode"]
+    pub stereo_mode: EnumParam<StereoMode>,
+
+    /// The amount used for the side channel in [`StereoMode::MidSide`].
+    #[id = "side_amount"]
+    pub side_amount: IntParam,
+
+    /// Detunes the left and right cascades in opposite directions, in cents.
+    #[id = "stereo_offset"]
+    pub stereo_offset: FloatParam,
+
+    /// When enabled both channels share the same frequency and the offset is ignored.
+    #[id = "stereo_link"]
+    pub stereo_link: BoolParam,
+
+    #[nested(group = "LFO")]
+    pub lfo: LfoParams,
+
+    #[nested(group = "Envelope")]
+    pub envelope: EnvelopeParams,
+
+    #[nested(group = "Keytrack")]
+    pub keytrack: KeytrackParams,
+
+    #[nested(group = "Pitch")]
+    pub pitch: PitchParams,
+
+    #[nested(group = "Zap")]
+    pub zap: ZapParams,
+
+    #[nested(group = "Feedback")]
+    pub feedback: FeedbackParams,
+
+    #[nested(group = "Transient")]
+    pub transient: TransientParams,
+
+    #[nested(group = "Spring")]
+    pub spring: SpringParams,
+
+    #[nested(group = "Multiband")]
+    pub multiband: MultibandParams,
+
+    #[id = "mix"]
+    pub mix: FloatParam,
+
+    /// How many samples pass between recomputing the disperser's coefficients from the smoothed
+    /// frequency and spread. Lower values follow fast automation more closely but cost more CPU.
+    #[id = "update_interval"]
+    pub update_interval: IntParam,
+
+    #[id = "oversampling"]
+    pub oversampling: EnumParam<Oversampling>,
+
+    /// Linear phase filters add latency, which is reported to the host.
+    #[id = "oversampling_filter"]
+    pub oversampling_filter: EnumParam<OversamplingFilter>,
+
+    #[id = "input_gain"]
+    pub input_gain: FloatParam,
+
+    #[id = "output_gain"]
+    pub output_gain: FloatParam,
+
+    #[id = "auto_gain"]
+    pub auto_gain: BoolParam,
 }
 
 impl Default for DisperserPlugin {
     fn default() -> Self {
         Self {
             params: Arc::new(DisperserParams::default()),
-            disperser: Disperser::<2>::new(44100),
+            cascades: Vec::new(),
+            oversampler: Oversampler::new(44100.0, 0),
+            dry_delay: LatencyDelay::new(0),
+            transient_delay: LatencyDelay::new(0),
+            oversampling_stages: 0,
+            latency: 0,
+            ringing_samples: 0,
             sample_rate: 44100.0,
+            shared_sample_rate: Arc::new(AtomicF32::new(44100.0)),
+            dsp_ctx: dsp_context(44100.0),
+            mono_to_stereo: false,
+            stereo_mode: StereoMode::LeftRight,
+
+            input_meter: Meter::new(44100.0, 0),
+            output_meter: Meter::new(44100.0, 0),
+            input_levels: Arc::new(MeterLevels::default()),
+            output_levels: Arc::new(MeterLevels::default()),
+            analyzer_taps: Arc::new(AnalyzerTaps::default()),
+            tap_decimator: TapDecimator::default(),
+            editor_open: false,
 
-            peak_meter_decay_weight: 1.0,
-            pre_signal: Arc::new(AtomicF32::new(util::MINUS_INFINITY_DB)),
-            post_signal: Arc::new(AtomicF32::new(util::MINUS_INFINITY_DB)),
+            auto_gain_rms_weight: 1.0,
+            auto_gain_smoothing_weight: 1.0,
+            pre_mean_square: 0.0,
+            post_mean_square: 0.0,
+            auto_gain: Arc::new(AtomicF32::new(1.0)),
+
+            envelope_follower: EnvelopeFollower::default(),
+            keytracker: Keytracker::default(),
+            pitch_detector: PitchDetector::new(44100.0),
+            pitch_learner: PitchLearner::default(),
+            pitch_learn: Arc::new(PitchLearn::default()),
+            pitch_target: None,
+            followed_frequency: None,
+            transient_detector: TransientDetector::new(44100.0),
+            zap: Zap::default(),
+            lfo: Lfo::default(),
+            lfo_phase_delta: 0.0,
+            modulated_values: Arc::new(ModulatedValues::default()),
         }
     }
 }
@@ -62,8 +366,8 @@ impl Default for DisperserParams {
                 "Frequency",
                 1145.0,
                 FloatRange::Skewed {
-                    min: 20.0,
-                    max: 20000.0,
+                    min: MIN_FREQUENCY,
+                    max: MAX_FREQUENCY,
                     factor: FloatRange::skew_factor(-2.0),
                 },
             )
@@ -74,24 +378,111 @@ impl Default for DisperserParams {
                 let semitones_rounded = semitones.round() as i32;
                 let midi_number = (57 + semitones_rounded) as u32;
                 format!("{} / {} Hz", format_note(midi_number), param_inner)
-            })),
+            }))
+            .with_smoother(SmoothingStyle::Logarithmic(50.0)),
 
             spread: FloatParam::new(
                 "Spread",
                 1145.0,
                 FloatRange::Skewed {
-                    min: 0.1,
-                    max: 2000.0,
+                    min: MIN_SPREAD,
+                    max: MAX_SPREAD,
                     factor: FloatRange::skew_factor(-2.0),
                 },
             )
-            .with_unit(" Hz"),
+            .with_unit(" Hz")
+            .with_smoother(SmoothingStyle::Logarithmic(50.0)),
+
+            amount: IntParam::new(
+                "Amount",
+                80,
+                IntRange::Linear {
+                    min: 0,
+                    max: MAX_AMOUNT,
+                },
+            ),
+
+            amount_transition: EnumParam::new("Amount Transition", AmountTransition::Crossfade),
+
+            stereo_mode: EnumParam::new("Stereo Mode", StereoMode::LeftRight),
+            side_amount: IntParam::new(
+                "Side Amount",
+                40,
+                IntRange::Linear {
+                    min: 0,
+                    max: MAX_AMOUNT,
+                },
+            ),
+
+            stereo_offset: FloatParam::new(
+                "Stereo Offset",
+                0.0,
+                FloatRange::Linear {
+                    min: -1200.0,
+                    max: 1200.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_step_size(1.0)
+            .with_unit(" ct"),
+            stereo_link: BoolParam::new("Stereo Link", false),
+
+            lfo: LfoParams::default(),
+            envelope: EnvelopeParams::default(),
+            keytrack: KeytrackParams::default(),
+            pitch: PitchParams::default(),
+            zap: ZapParams::default(),
+            feedback: FeedbackParams::default(),
+            transient: TransientParams::default(),
+            spring: SpringParams::default(),
+            multiband: MultibandParams::default(),
+
+            mix: FloatParam::new("Mix", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            update_interval: IntParam::new(
+                "Update Interval",
+                16,
+                IntRange::Linear { min: 1, max: 128 },
+            )
+            .with_unit(" smp")
+            .non_automatable(),
+
+            oversampling: EnumParam::new("Oversampling", Oversampling::X1).non_automatable(),
+            oversampling_filter: EnumParam::new(
+                "Oversampling Filter",
+                OversamplingFilter::MinimumPhase,
+            )
+            .non_automatable(),
+
+            input_gain: gain_param("Input Gain"),
+            output_gain: gain_param("Output Gain"),
 
-            amount: IntParam::new("Amount", 80, IntRange::Linear { min: 0, max: 100 }),
+            auto_gain: BoolParam::new("Auto Gain", false),
         }
     }
 }
 
+/// A gain parameter in decibels, shared by the input and output stages.
+fn gain_param(name: &str) -> FloatParam {
+    FloatParam::new(
+        name,
+        util::db_to_gain(0.0),
+        FloatRange::Skewed {
+            min: util::db_to_gain(-24.0),
+            max: util::db_to_gain(24.0),
+            factor: FloatRange::gain_skew_factor(-24.0, 24.0),
+        },
+    )
+    .with_smoother(SmoothingStyle::Logarithmic(50.0))
+    .with_unit(" dB")
+    .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+    .with_string_to_value(formatters::s2v_f32_gain_to_db())
+}
+
 const NOTE_NAMES: [&str; 12] = [
     "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
 ];
@@ -111,12 +502,47 @@ impl Plugin for DisperserPlugin {
 
     const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
-    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
-        main_input_channels: NonZeroU32::new(2),
-        main_output_channels: NonZeroU32::new(2),
-        ..AudioIOLayout::const_default()
-    }];
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+            aux_input_ports: &[new_nonzero_u32(2)],
+            names: SIDECHAIN_PORT_NAMES,
+            ..AudioIOLayout::const_default()
+        },
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(1),
+            main_output_channels: NonZeroU32::new(1),
+            aux_input_ports: &[new_nonzero_u32(1)],
+            names: SIDECHAIN_PORT_NAMES,
+            ..AudioIOLayout::const_default()
+        },
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(1),
+            main_output_channels: NonZeroU32::new(2),
+            aux_input_ports: &[new_nonzero_u32(1)],
+            names: SIDECHAIN_PORT_NAMES,
+            ..AudioIOLayout::const_default()
+        },
+        // 5.1
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(6),
+            main_output_channels: NonZeroU32::new(6),
+            aux_input_ports: &[new_nonzero_u32(2)],
+            names: SIDECHAIN_PORT_NAMES,
+            ..AudioIOLayout::const_default()
+        },
+        // 7.1
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(8),
+            main_output_channels: NonZeroU32::new(8),
+            aux_input_ports: &[new_nonzero_u32(2)],
+            names: SIDECHAIN_PORT_NAMES,
+            ..AudioIOLayout::const_default()
+        },
+    ];
 
+    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
     type SysExMessage = ();
@@ -129,116 +555,717 @@ impl Plugin for DisperserPlugin {
     fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
         editor::create(
             self.params.clone(),
-            self.pre_signal.clone(),
-            self.post_signal.clone(),
+            self.input_levels.clone(),
+            self.output_levels.clone(),
+            self.analyzer_taps.clone(),
+            self.auto_gain.clone(),
+            self.modulated_values.clone(),
+            self.pitch_learn.clone(),
+            self.shared_sample_rate.clone(),
             self.params.editor_state.clone(),
         )
     }
 
     fn initialize(
         &mut self,
-        _audio_io_layout: &AudioIOLayout,
+        audio_io_layout: &AudioIOLayout,
         buffer_config: &BufferConfig,
-        _context: &mut impl InitContext<Self>,
+        context: &mut impl InitContext<Self>,
     ) -> bool {
         self.sample_rate = buffer_config.sample_rate;
-        self.disperser = Disperser::<2>::new(self.sample_rate as usize);
+        self.shared_sample_rate
+            .store(self.sample_rate, std::sync::atomic::Ordering::Relaxed);
+        self.dsp_ctx = dsp_context(self.sample_rate);
+
+        let input_channels = audio_io_layout
+            .main_input_channels
+            .map(NonZeroU32::get)
+            .unwrap_or(0);
+        let output_channels = audio_io_layout
+            .main_output_channels
+            .map(NonZeroU32::get)
+            .unwrap_or(0);
+        self.mono_to_stereo = input_channels == 1 && output_channels == 2;
+        self.transient_detector = TransientDetector::new(self.sample_rate);
+        self.pitch_detector = PitchDetector::new(self.sample_rate);
+        self.cascades = (0..=MAX_STAGES)
+            .map(|stages| {
+                Cascades::new(
+                    self.sample_rate * (1 << stages) as f32,
+                    output_channels as usize,
+                )
+            })
+            .collect();
+        self.oversampler = Oversampler::new(self.sample_rate, output_channels as usize);
+        self.dry_delay = LatencyDelay::new(output_channels as usize);
+        self.transient_delay = LatencyDelay::new(output_channels as usize);
 
-        self.peak_meter_decay_weight = 0.25f64
-            .powf((buffer_config.sample_rate as f64 * PEAK_METER_DECAY_MS / 1000.0).recip())
-            as f32;
+        self.oversampling_stages = self.params.oversampling.value().stages();
+        self.latency = latency_samples(
+            self.oversampling_stages,
+            self.params.oversampling_filter.value(),
+        );
+        context.set_latency_samples(self.latency);
+
+        self.input_meter = Meter::new(self.sample_rate, output_channels as usize);
+        self.output_meter = Meter::new(self.sample_rate, output_channels as usize);
+        self.tap_decimator = TapDecimator::new(self.sample_rate, &self.analyzer_taps);
+
+        self.auto_gain_rms_weight = one_pole_weight(buffer_config.sample_rate, AUTO_GAIN_RMS_MS);
+        self.auto_gain_smoothing_weight =
+            one_pole_weight(buffer_config.sample_rate, AUTO_GAIN_SMOOTHING_MS);
 
         true
     }
 
+    fn reset(&mut self) {
+        self.pre_mean_square = 0.0;
+        self.post_mean_square = 0.0;
+        self.input_meter.reset();
+        self.output_meter.reset();
+        self.tap_decimator.reset();
+        self.lfo.reset();
+        self.envelope_follower.reset();
+        self.keytracker.reset();
+        self.transient_detector.reset();
+        self.pitch_detector.reset();
+        self.pitch_target = None;
+        self.followed_frequency = None;
+        self.ringing_samples = 0;
+        self.zap.reset();
+        self.oversampler.reset();
+        self.dry_delay.reset();
+        self.transient_delay.reset();
+        for cascades in &mut self.cascades {
+            cascades.reset();
+        }
+    }
+
     fn process(
         &mut self,
         buffer: &mut Buffer,
-        _aux: &mut AuxiliaryBuffers,
-        _context: &mut impl ProcessContext<Self>,
+        aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        let freq = self.params.frequency.value();
-        let spread = self.params.spread.value();
-        let amount = self.params.amount.value();
+        let transport = context.transport();
+        let tempo = transport.tempo;
+        let song_position = if transport.playing {
+            transport.pos_beats()
+        } else {
+            None
+        };
+
+        let editor_open = self.params.editor_state.is_open();
+
+        self.process_block(buffer, aux, context, tempo, song_position, editor_open)
+    }
+}
 
-        self.disperser.set_filter_parameters(freq, spread);
-        self.disperser.set_biquad_count(amount as usize);
+/// The context the cascades are processed with. None of the effects use MIDI, so the event list
+/// stays empty and is never grown on the audio thread.
+fn dsp_context(sample_rate: f32) -> Box<dyn DspContext> {
+    let mut info = ProcessInfos::new();
+    info.sample_rate = sample_rate as usize;
 
-        let mut info = ProcessInfos::new();
-        info.sample_rate = self.sample_rate as usize;
+    Box::new(SimpleContext {
+        info,
+        midi_events: Vec::new(),
+    })
+}
 
-        let simple_ctx = SimpleContext {
-            info,
-            midi_events: Vec::new(),
+/// A rough estimate of the cascade's group delay at its centre frequency, in samples. Each second
+/// order allpass stage delays its centre by about `1 / (pi * bandwidth)` seconds.
+fn cascade_group_delay(amount: i32, spread: f32, sample_rate: f32) -> f32 {
+    amount as f32 * sample_rate / (std::f32::consts::PI * spread)
+}
+
+/// The one-pole weight for gliding over `num_samples` with a `glide_ms` time constant.
+fn glide_weight(num_samples: usize, sample_rate: f32, glide_ms: f32) -> f32 {
+    if glide_ms > 0.0 {
+        (-(num_samples as f32) / (sample_rate * glide_ms / 1000.0)).exp()
+    } else {
+        0.0
+    }
+}
+
+/// The weight for a one-pole filter that settles within `time_ms`.
+fn one_pole_weight(sample_rate: f32, time_ms: f64) -> f32 {
+    0.25f64.powf((sample_rate as f64 * time_ms / 1000.0).recip()) as f32
+}
+
+/// The gain that brings the post signal back to the pre signal's RMS. Holds `current` while there's
+/// nothing meaningful to compare, so the compensation doesn't drift during silence.
+fn auto_gain_target(pre_mean_square: f32, post_mean_square: f32, current: f32) -> f32 {
+    const SILENCE: f32 = 1e-10;
+    if pre_mean_square < SILENCE || post_mean_square < SILENCE {
+        return current;
+    }
+
+    let max_gain = util::db_to_gain(AUTO_GAIN_MAX_DB);
+    (pre_mean_square / post_mean_square)
+        .sqrt()
+        .clamp(max_gain.recip(), max_gain)
+}
+
+impl DisperserPlugin {
+    /// Everything `process()` does, with the transport information it needs passed in directly.
+    /// `song_position` is the position in beats while the host is playing.
+    fn process_block(
+        &mut self,
+        buffer: &mut Buffer,
+        aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+        tempo: Option<f64>,
+        song_position: Option<f64>,
+        editor_open: bool,
+    ) -> ProcessStatus {
+        self.editor_open = editor_open;
+
+        // NOTE: With `SAMPLE_ACCURATE_AUTOMATION` the wrapper already splits the buffer at every
+        //       parameter change, so we only need to split further for the coefficient updates
+        let update_interval = self.params.update_interval.value().max(1) as usize;
+
+        let oversampling_filter = self.params.oversampling_filter.value();
+        let oversampling_stages = self.params.oversampling.value().stages();
+        if oversampling_stages != self.oversampling_stages {
+            // The other cascades haven't run in a while, don't let them play what they still hold.
+            // Their stage counts snap to the current amount on the next update instead of fading
+            // over from whatever they were left at.
+            self.cascades[oversampling_stages].reset();
+            self.oversampler.reset();
+            self.dry_delay.reset();
+            self.transient_delay.reset();
+            self.oversampling_stages = oversampling_stages;
+        }
+
+        let latency = latency_samples(oversampling_stages, oversampling_filter);
+        if latency != self.latency {
+            context.set_latency_samples(latency);
+            self.latency = latency;
+        }
+
+        let cascades = &mut self.cascades[oversampling_stages];
+        cascades
+            .feedback_loop
+            .set_damping(cascades.sample_rate, self.params.feedback.damping.value());
+        self.envelope_follower.set_times(
+            self.sample_rate,
+            self.params.envelope.attack.value(),
+            self.params.envelope.release.value(),
+        );
+
+        self.lfo_phase_delta = self.params.lfo.phase_delta(self.sample_rate, tempo);
+        // Synced LFOs follow the song position so they stay on the grid while the host plays
+        if self.params.lfo.sync.value()
+            && let Some(pos_beats) = song_position
+        {
+            let cycle_beats = self.params.lfo.division.value().beats();
+            self.lfo.set_phase((pos_beats / cycle_beats).fract() as f32);
+        }
+
+        let auto_gain_enabled = self.params.auto_gain.value();
+        let mut auto_gain = self.auto_gain.load(std::sync::atomic::Ordering::Relaxed);
+
+        // The wrapper leaves the extra output channel silent, so the mono input is spread to both
+        // sides before it goes through the (per-channel) cascades
+        if self.mono_to_stereo && buffer.channels() == 2 {
+            let (input, outputs) = buffer.as_slice().split_at_mut(1);
+            outputs[0].copy_from_slice(input[0]);
+        }
+
+        let sidechain = match self.params.envelope.source.value() {
+            EnvelopeSource::Sidechain => aux.inputs.first().map(|input| input.as_slice_immutable()),
+            EnvelopeSource::Input => None,
         };
 
-        let mut dsp_ctx: Box<dyn DspContext> = Box::new(simple_ctx);
+        let num_samples = buffer.samples();
+        let channels = buffer
+            .channels()
+            .min(self.cascades[oversampling_stages].dispersers.len());
+        let num_channels = channels.max(1) as f32;
 
-        let mut amplitude = 0.0;
-        let mut original_amplitude = 0.0;
-        let channels = buffer.channels();
+        self.pitch_learner.poll(&self.pitch_learn, self.sample_rate);
+        let pitch_follow = self.params.pitch.follow.value();
 
-        // TODO: impl for other channels
-        if channels == 2 {
-            let samples = buffer.as_slice();
-            let (left_chan, right_chan) = samples.split_at_mut(1);
-            let left_samples = &mut left_chan[0];
-            let right_samples = &mut right_chan[0];
+        let transient_preserve = self.params.transient.preserve.value();
+        let transient_sensitivity = self.params.transient.sensitivity.value();
 
-            for (l, r) in left_samples.iter_mut().zip(right_samples.iter_mut()) {
-                let mut frame = [*l, *r];
-                let other_inputs: &[&[f32; 2]] = &[];
+        let zap_trigger = self.params.zap.trigger.value();
+        let zap_threshold = self.params.zap.transient_threshold.value();
+
+        let mut block_input_peak = 0.0f32;
+        let mut next_event = context.next_event();
+        for (sample_idx, mut channel_samples) in buffer.iter_samples().enumerate() {
+            while let Some(event) = next_event {
+                if event.timing() > sample_idx as u32 {
+                    break;
+                }
 
-                let current_amp = l.abs().max(r.abs());
-                if current_amp > original_amplitude {
-                    original_amplitude = current_amp;
+                match event {
+                    NoteEvent::NoteOn { note, .. } => {
+                        self.keytracker.note_on(note);
+                        if zap_trigger == ZapTrigger::Midi {
+                            self.zap.trigger();
+                        }
+                    }
+                    NoteEvent::NoteOff { note, .. } => self.keytracker.note_off(note),
+                    _ => (),
                 }
 
-                self.disperser
-                    .process(&mut frame, other_inputs, &mut dsp_ctx);
+                next_event = context.next_event();
+            }
+
+            if sample_idx % update_interval == 0 {
+                self.update_filter_parameters(update_interval.min(num_samples - sample_idx));
+            }
 
-                *l = frame[0];
-                *r = frame[1];
+            let mix = self.params.mix.smoothed.next();
+            let input_gain = self.params.input_gain.smoothed.next();
+            let output_gain = self.params.output_gain.smoothed.next();
 
-                let current_amp = l.abs().max(r.abs());
-                if current_amp > amplitude {
-                    amplitude = current_amp;
+            let mut dry = [0.0; MAX_CHANNELS];
+            let mut input_peak = 0.0f32;
+            for (dry_sample, sample) in dry.iter_mut().zip(channel_samples.iter_mut()) {
+                *dry_sample = *sample * input_gain;
+                input_peak = dry_sample.abs().max(input_peak);
+            }
+            self.input_meter.process(&dry[..channels]);
+            block_input_peak = block_input_peak.max(input_peak);
+
+            if self.transient_detector.process(input_peak, zap_threshold)
+                && zap_trigger == ZapTrigger::Transient
+            {
+                self.zap.trigger();
+            }
+
+            // The detector only runs while something needs it
+            if pitch_follow || self.pitch_learner.is_active() {
+                let mono = dry[..channels].iter().sum::<f32>() / num_channels;
+                if let Some(pitch) = self.pitch_detector.process(mono) {
+                    self.pitch_learner.add_estimate(pitch);
+                    self.pitch_target = Some(pitch);
                 }
+                self.pitch_learner.advance(&self.pitch_learn);
+            }
+
+            match sidechain {
+                Some(sidechain) => {
+                    let sidechain_peak = sidechain
+                        .iter()
+                        .filter_map(|channel| channel.get(sample_idx))
+                        .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+                    self.envelope_follower.process(sidechain_peak);
+                }
+                None => self.envelope_follower.process(input_peak),
+            }
+
+            // With transient preservation the part of the input above the sustain level is taken
+            // out before the cascade and added back after it. Both parts add up to the input.
+            let transient_amount = self.params.transient.amount.smoothed.next();
+            let transient_gain = if transient_preserve {
+                self.transient_detector
+                    .transient_amount(transient_sensitivity)
+            } else {
+                0.0
+            };
+            let mut transient = [0.0; MAX_CHANNELS];
+            for (transient_sample, dry_sample) in transient.iter_mut().zip(dry) {
+                *transient_sample = dry_sample * transient_gain;
+            }
+
+            let feedback = self.params.feedback.gain.smoothed.next();
+            let mut wet = dry;
+            for (wet_sample, transient_sample) in wet.iter_mut().zip(transient) {
+                *wet_sample -= transient_sample;
+            }
+            if oversampling_stages == 0 {
+                self.process_frame(&mut wet[..channels], feedback);
+            } else {
+                self.process_oversampled_frame(&mut wet[..channels], feedback, oversampling_filter);
+            }
+
+            // The dry signal and the transients need to wait for the oversampling filters, or the
+            // mix would comb filter
+            if self.latency > 0 {
+                self.dry_delay.process(&mut dry[..channels], self.latency);
+                self.transient_delay
+                    .process(&mut transient[..channels], self.latency);
+            }
+
+            for (wet_sample, transient_sample) in wet.iter_mut().zip(transient) {
+                *wet_sample += transient_sample * transient_amount;
+            }
+
+            let mut pre_square_sum = 0.0;
+            let mut post_square_sum = 0.0;
+            for ((sample, dry), wet) in channel_samples.iter_mut().zip(dry).zip(wet) {
+                // A plain linear blend. The wet path has the same magnitude response as the dry one,
+                // but summing it with a phase shifted copy comb filters, so partial mixes notch
+                // and boost depending on frequency
+                *sample = dry + (wet - dry) * mix;
+
+                pre_square_sum += dry * dry;
+                post_square_sum += *sample * *sample;
+            }
+
+            self.pre_mean_square = self.pre_mean_square * self.auto_gain_rms_weight
+                + pre_square_sum / num_channels * (1.0 - self.auto_gain_rms_weight);
+            self.post_mean_square = self.post_mean_square * self.auto_gain_rms_weight
+                + post_square_sum / num_channels * (1.0 - self.auto_gain_rms_weight);
+
+            let target_gain = if auto_gain_enabled {
+                auto_gain_target(self.pre_mean_square, self.post_mean_square, auto_gain)
+            } else {
+                1.0
+            };
+            auto_gain = auto_gain * self.auto_gain_smoothing_weight
+                + target_gain * (1.0 - self.auto_gain_smoothing_weight);
+
+            let mut output = [0.0; MAX_CHANNELS];
+            for (output_sample, sample) in output.iter_mut().zip(channel_samples.iter_mut()) {
+                *sample *= auto_gain * output_gain;
+                *output_sample = *sample;
+            }
+            self.output_meter.process(&output[..channels]);
+            // The analyzers don't need to be fed while nobody's looking
+            if self.editor_open {
+                self.tap_decimator.push(
+                    &self.analyzer_taps,
+                    &dry[..channels],
+                    &wet[..channels],
+                    &output[..channels],
+                );
             }
         }
 
-        for channel_samples in buffer.iter_samples() {
-            if self.params.editor_state.is_open() {
-                let num_samples = channel_samples.len();
-                original_amplitude = (original_amplitude / num_samples as f32).abs();
-                let current_peak_meter = self.pre_signal.load(std::sync::atomic::Ordering::Relaxed);
-                let new_peak_meter = if original_amplitude > current_peak_meter {
-                    original_amplitude
-                } else {
-                    current_peak_meter * self.peak_meter_decay_weight
-                        + original_amplitude * (1.0 - self.peak_meter_decay_weight)
-                };
+        self.auto_gain
+            .store(auto_gain, std::sync::atomic::Ordering::Relaxed);
 
-                self.pre_signal
-                    .store(new_peak_meter, std::sync::atomic::Ordering::Relaxed);
+        self.input_meter.publish(&self.input_levels);
+        self.output_meter.publish(&self.output_levels);
 
-                let num_samples = channel_samples.len();
-                amplitude = (amplitude / num_samples as f32).abs();
-                let current_peak_meter =
-                    self.post_signal.load(std::sync::atomic::Ordering::Relaxed);
-                let new_peak_meter = if amplitude > current_peak_meter {
-                    amplitude
+        // Even without feedback, whatever went in last keeps coming out of the cascades for
+        // about their group delay
+        let max_tail_samples = (MAX_TAIL_SECONDS * self.sample_rate) as u32;
+        self.ringing_samples = if block_input_peak > util::db_to_gain(TAIL_SILENCE_DB) {
+            (self.cascade_group_delay() as u32).min(max_tail_samples)
+        } else {
+            self.ringing_samples.saturating_sub(num_samples as u32)
+        };
+
+        let cascades = &self.cascades[oversampling_stages];
+        let feedback_tail_samples = if cascades.spring_tank.enabled() {
+            cascades
+                .spring_tank
+                .tail_samples(&self.params.spring, self.sample_rate)
+        } else {
+            // The loop's one sample delay runs at the oversampled rate
+            let loop_samples =
+                ((1 << oversampling_stages) as f32).recip() + self.cascade_group_delay();
+            cascades
+                .feedback_loop
+                .tail_samples(self.params.feedback.gain.value(), loop_samples)
+        };
+
+        match feedback_tail_samples.max(self.ringing_samples) {
+            0 => ProcessStatus::Normal,
+            tail_samples => ProcessStatus::Tail(
+                tail_samples
+                    .min(max_tail_samples)
+                    .saturating_add(self.latency),
+            ),
+        }
+    }
+
+    /// The group delay of the longest cascade the current parameters use, in samples.
+    fn cascade_group_delay(&self) -> f32 {
+        let multiband = &self.params.multiband;
+        match multiband.bands.value() as usize {
+            1 => cascade_group_delay(
+                self.params.amount.value(),
+                self.params.spread.value(),
+                self.sample_rate,
+            ),
+            bands => multiband.band[..bands]
+                .iter()
+                .map(|band| {
+                    cascade_group_delay(band.amount.value(), band.spread.value(), self.sample_rate)
+                })
+                .fold(0.0, f32::max),
+        }
+    }
+
+    /// Advance the frequency and spread smoothers by `num_samples` and recompute the disperser's
+    /// coefficients if anything changed.
+    fn update_filter_parameters(&mut self, num_samples: usize) {
+        let freq = self.params.frequency.smoothed.next_step(num_samples as u32);
+        let spread = self.params.spread.smoothed.next_step(num_samples as u32);
+        let amount = self.params.amount.value();
+
+        // Pitch follow glides in octaves, like the keytracker does in semitones
+        if let Some(target) = self.pitch_target {
+            let weight = glide_weight(
+                num_samples,
+                self.sample_rate,
+                self.params.pitch.glide.value(),
+            );
+            let current = self.followed_frequency.unwrap_or(target);
+            self.followed_frequency = Some(target * (current / target).powf(weight));
+        }
+        let followed = self.params.pitch.follow.value() && self.followed_frequency.is_some();
+        let freq = match self.followed_frequency {
+            Some(followed_frequency) if followed => followed_frequency,
+            _ => freq,
+        };
+
+        let keytracked_note = self.keytracker.next_note(glide_weight(
+            num_samples,
+            self.sample_rate,
+            self.params.keytrack.glide.value(),
+        ));
+        let keytracked = self.params.keytrack.enabled.value() && keytracked_note.is_some();
+        let freq = match keytracked_note {
+            Some(note) if keytracked => note_to_freq(note + self.params.keytrack.transpose()),
+            _ => freq,
+        };
+
+        // A running zap takes over the frequency, and hands it back to the parameter when it's done
+        let zap_frequency =
+            self.zap
+                .next_frequency(&self.params.zap, freq, self.sample_rate, num_samples);
+        let zapped = self.params.zap.trigger.value() != ZapTrigger::Off && zap_frequency.is_some();
+        let frequency_overridden = followed || keytracked || zapped;
+        let freq = match zap_frequency {
+            Some(zap_frequency) if zapped => zap_frequency,
+            _ => freq,
+        };
+
+        let modulation = self.next_modulation(num_samples);
+        let freq =
+            (freq * 2.0f32.powf(modulation.frequency_octaves)).clamp(MIN_FREQUENCY, MAX_FREQUENCY);
+        let spread =
+            (spread * 2.0f32.powf(modulation.spread_octaves)).clamp(MIN_SPREAD, MAX_SPREAD);
+        let amount = (amount as f32 + modulation.amount)
+            .round()
+            .clamp(0.0, MAX_AMOUNT as f32) as usize;
+
+        if self.editor_open {
+            let to_display = |modulated: bool, normalized: f32| {
+                if modulated { normalized } else { -1.0 }
+            };
+            self.modulated_values.frequency.store(
+                to_display(
+                    frequency_overridden || modulation.frequency_octaves != 0.0,
+                    self.params.frequency.preview_normalized(freq),
+                ),
+                std::sync::atomic::Ordering::Relaxed,
+            );
+            self.modulated_values.spread.store(
+                to_display(
+                    modulation.spread_octaves != 0.0,
+                    self.params.spread.preview_normalized(spread),
+                ),
+                std::sync::atomic::Ordering::Relaxed,
+            );
+            self.modulated_values.amount.store(
+                to_display(
+                    modulation.amount != 0.0,
+                    self.params.amount.preview_normalized(amount as i32),
+                ),
+                std::sync::atomic::Ordering::Relaxed,
+            );
+        }
+
+        let side_amount = self.params.side_amount.value() as usize;
+        let crossfade = self.params.amount_transition.value() == AmountTransition::Crossfade;
+        self.stereo_mode = self.params.stereo_mode.value();
+
+        let stereo_offset = self
+            .params
+            .stereo_offset
+            .smoothed
+            .next_step(num_samples as u32);
+
+        let cascades = &mut self.cascades[self.oversampling_stages];
+        let stereo = cascades.dispersers.len() == 2;
+        let mid_side = stereo && self.stereo_mode == StereoMode::MidSide;
+        // Each side is moved by half of the offset so the pair stays centred on `freq`
+        let offset_ratio = if stereo
+            && self.stereo_mode == StereoMode::LeftRight
+            && !self.params.stereo_link.value()
+        {
+            2.0f32.powf(stereo_offset / 2400.0)
+        } else {
+            1.0
+        };
+
+        // The bands follow the same per channel offsets. The side channel's bands are moved by
+        // the difference between the side amount and the amount.
+        let mut band_offsets = [BandOffsets::default(); MAX_CHANNELS];
+        for ((channel, disperser), band_offsets) in cascades
+            .dispersers
+            .iter_mut()
+            .enumerate()
+            .zip(&mut band_offsets)
+        {
+            let (amount, amount_offset) = if mid_side && channel == 1 {
+                (
+                    side_amount,
+                    side_amount as f32 - self.params.amount.value() as f32,
+                )
+            } else {
+                (amount, modulation.amount)
+            };
+            let channel_ratio = match channel {
+                0 if stereo => offset_ratio.recip(),
+                1 => offset_ratio,
+                _ => 1.0,
+            };
+            let freq = (freq * channel_ratio).clamp(MIN_FREQUENCY, MAX_FREQUENCY);
+
+            disperser.set_filter_parameters(freq, spread);
+            disperser.set_biquad_count(amount, crossfade);
+
+            *band_offsets = BandOffsets {
+                frequency_ratio: 2.0f32.powf(modulation.frequency_octaves) * channel_ratio,
+                amount_offset,
+            };
+        }
+
+        cascades.multiband.update(
+            &self.params.multiband,
+            cascades.sample_rate,
+            &band_offsets[..cascades.dispersers.len()],
+            2.0f32.powf(modulation.spread_octaves),
+            crossfade,
+        );
+
+        cascades
+            .spring_tank
+            .set_enabled(self.params.spring.enabled.value());
+        if cascades.spring_tank.enabled() {
+            cascades.spring_tank.update(
+                &self.params.spring,
+                cascades.sample_rate,
+                spread,
+                amount,
+                crossfade,
+                self.params.feedback.damping.value(),
+                cascade_group_delay(amount as i32, spread, cascades.sample_rate),
+            );
+        }
+    }
+
+    /// Advance the modulation sources by `num_samples` and sum up how far they move the parameters.
+    fn next_modulation(&mut self, num_samples: usize) -> Modulation {
+        let mut modulation = Modulation::default();
+
+        let lfo_depth = self.params.lfo.depth.smoothed.next_step(num_samples as u32);
+        let lfo_value = self.lfo.next(
+            self.params.lfo.shape.value(),
+            self.lfo_phase_delta * num_samples as f32,
+        );
+        let lfo_octaves = lfo_value * lfo_depth * LFO_RANGE_OCTAVES;
+        match self.params.lfo.target.value() {
+            LfoTarget::Frequency => modulation.frequency_octaves += lfo_octaves,
+            LfoTarget::Spread => modulation.spread_octaves += lfo_octaves,
+        }
+
+        let envelope_depth = self
+            .params
+            .envelope
+            .depth
+            .smoothed
+            .next_step(num_samples as u32);
+        let envelope_amount = self
+            .envelope_follower
+            .amount_above(self.params.envelope.threshold.value())
+            * envelope_depth;
+        modulation.add(self.params.envelope.target.value(), envelope_amount);
+
+        modulation
+    }
+
+    /// Run one frame through the cascades, encoding stereo signals to mid/side first if needed. In
+    /// spring mode the spring tank takes the place of the plain cascades and the feedback path, with
+    /// more than one band the multiband cascades take the place of the plain cascades.
+    fn process_frame(&mut self, frame: &mut [f32], feedback: f32) {
+        let cascades = &mut self.cascades[self.oversampling_stages];
+        let dsp_ctx = &mut self.dsp_ctx;
+
+        let mid_side = frame.len() == 2 && self.stereo_mode != StereoMode::LeftRight;
+        if mid_side {
+            let (left, right) = (frame[0], frame[1]);
+            frame[0] = (left + right) * 0.5;
+            frame[1] = (left - right) * 0.5;
+        }
+
+        for (channel, (sample, disperser)) in frame
+            .iter_mut()
+            .zip(cascades.dispersers.iter_mut())
+            .enumerate()
+        {
+            if mid_side && !self.stereo_mode.processes_channel(channel) {
+                continue;
+            }
+
+            if cascades.spring_tank.enabled() {
+                *sample = cascades.spring_tank.process(channel, *sample, dsp_ctx);
+            } else {
+                let input = cascades.feedback_loop.input(channel, *sample, feedback);
+                let output = if cascades.multiband.enabled() {
+                    cascades.multiband.process(channel, input, dsp_ctx)
                 } else {
-                    current_peak_meter * self.peak_meter_decay_weight
-                        + amplitude * (1.0 - self.peak_meter_decay_weight)
+                    let mut mono_frame = [input];
+                    disperser.process(&mut mono_frame, dsp_ctx);
+                    mono_frame[0]
                 };
+                cascades.feedback_loop.output(channel, output);
+                *sample = output;
+            }
+        }
+
+        if mid_side {
+            let (mid, side) = (frame[0], frame[1]);
+            frame[0] = mid + side;
+            frame[1] = mid - side;
+        }
+    }
+
+    /// [`process_frame()`][Self::process_frame()] at the oversampled rate.
+    fn process_oversampled_frame(
+        &mut self,
+        frame: &mut [f32],
+        feedback: f32,
+        filter: OversamplingFilter,
+    ) {
+        let num_stages = self.oversampling_stages;
 
-                self.post_signal
-                    .store(new_peak_meter, std::sync::atomic::Ordering::Relaxed);
+        let mut upsampled = [[0.0; MAX_FACTOR]; MAX_CHANNELS];
+        for (channel, sample) in frame.iter().enumerate() {
+            upsampled[channel] = self
+                .oversampler
+                .upsample(channel, *sample, num_stages, filter);
+        }
+
+        for idx in 0..1 << num_stages {
+            let mut oversampled_frame = [0.0; MAX_CHANNELS];
+            for (sample, upsampled) in oversampled_frame.iter_mut().zip(&upsampled) {
+                *sample = upsampled[idx];
+            }
+
+            self.process_frame(&mut oversampled_frame[..frame.len()], feedback);
+
+            for (sample, upsampled) in oversampled_frame.iter().zip(&mut upsampled) {
+                upsampled[idx] = *sample;
             }
         }
 
-        ProcessStatus::Normal
+        for (channel, sample) in frame.iter_mut().enumerate() {
+            *sample = self
+                .oversampler
+                .downsample(channel, upsampled[channel], num_stages, filter);
+        }
     }
 }
 
@@ -250,6 +1277,8 @@ impl ClapPlugin for DisperserPlugin {
     const CLAP_FEATURES: &'static [ClapFeature] = &[
         ClapFeature::AudioEffect,
         ClapFeature::Stereo,
+        ClapFeature::Mono,
+        ClapFeature::Surround,
         ClapFeature::Phaser,
     ];
 }
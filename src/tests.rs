@@ -0,0 +1,473 @@
+//! Runs the plugin under `assert_no_alloc` while sweeping every parameter that changes how the
+//! signal gets processed, so anything that allocates on the audio thread aborts the test.
+
+use assert_no_alloc::assert_no_alloc;
+use nih_plug::prelude::*;
+use std::sync::Arc;
+
+use crate::dsp::envelope::{EnvelopeSource, ModulationTarget};
+use crate::dsp::multiband::MAX_BANDS;
+use crate::dsp::oversampling::{Oversampling, OversamplingFilter};
+use crate::dsp::zap::ZapTrigger;
+use crate::{
+    AmountTransition, DisperserParams, DisperserPlugin, MAX_AMOUNT, MAX_FREQUENCY, MAX_SPREAD,
+    MIN_FREQUENCY, MIN_SPREAD, StereoMode,
+};
+
+// With `assert_process_allocs` nih_plug already installs this allocator in debug builds
+#[cfg(not(all(debug_assertions, feature = "assert_process_allocs")))]
+#[global_allocator]
+static ALLOCATOR: assert_no_alloc::AllocDisabler = assert_no_alloc::AllocDisabler;
+
+const SAMPLE_RATE: f32 = 48000.0;
+const BLOCK_SIZE: usize = 64;
+/// The amount sweeps go up and back down in steps this large.
+const AMOUNT_STEP: usize = 20;
+/// The smoothed parameters' targets go up and back down over this many blocks.
+const RAMP_BLOCKS: usize = 100;
+/// A note starts with every burst of the test signal and stops halfway through it.
+const NOTE_INTERVAL: usize = 1000;
+/// Long enough for a pitch learn to finish and publish its result.
+const LEARN_BLOCKS: usize = 3 * SAMPLE_RATE as usize / BLOCK_SIZE + 1;
+
+/// The parameters the sweeps change. Everything else stays at its default.
+#[derive(Clone, Copy)]
+struct Settings {
+    amount: i32,
+    amount_transition: AmountTransition,
+    oversampling: Oversampling,
+    oversampling_filter: OversamplingFilter,
+    stereo_mode: StereoMode,
+    bands: i32,
+    spring: bool,
+    feedback: f32,
+    lfo_depth: f32,
+    envelope_source: EnvelopeSource,
+    envelope_target: ModulationTarget,
+    envelope_depth: f32,
+    keytrack: bool,
+    zap_trigger: ZapTrigger,
+    pitch_follow: bool,
+    /// Starts a new pitch learn whenever the last one is done.
+    pitch_learn: bool,
+    transient_preserve: bool,
+    /// Feeds the analyzers and the modulated values like an open editor would.
+    editor_open: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            amount: 0,
+            amount_transition: AmountTransition::Crossfade,
+            oversampling: Oversampling::X1,
+            oversampling_filter: OversamplingFilter::MinimumPhase,
+            stereo_mode: StereoMode::LeftRight,
+            bands: 1,
+            spring: false,
+            feedback: 0.0,
+            lfo_depth: 0.0,
+            envelope_source: EnvelopeSource::Input,
+            envelope_target: ModulationTarget::Frequency,
+            envelope_depth: 0.0,
+            keytrack: false,
+            zap_trigger: ZapTrigger::Off,
+            pitch_follow: false,
+            pitch_learn: false,
+            transient_preserve: false,
+            editor_open: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Parameters can only be set through a plugin wrapper, so every change builds a new set with
+    /// the values as their defaults instead. The smoothed parameters use `ramp` and the settings as
+    /// their targets, and continue from wherever `previous`'s smoothers were, so they keep ramping
+    /// like they would under automation.
+    fn params(&self, previous: &DisperserParams, ramp: f32) -> Arc<DisperserParams> {
+        let mut params = DisperserParams::default();
+        params.frequency = smoothed_param(
+            "Frequency",
+            MIN_FREQUENCY * (MAX_FREQUENCY / MIN_FREQUENCY).powf(ramp),
+            MIN_FREQUENCY,
+            MAX_FREQUENCY,
+        );
+        params.spread = smoothed_param(
+            "Spread",
+            MIN_SPREAD * (MAX_SPREAD / MIN_SPREAD).powf(1.0 - ramp),
+            MIN_SPREAD,
+            MAX_SPREAD,
+        );
+        params.amount = IntParam::new(
+            "Amount",
+            self.amount,
+            IntRange::Linear {
+                min: 0,
+                max: MAX_AMOUNT,
+            },
+        );
+        params.side_amount = IntParam::new(
+            "Side Amount",
+            MAX_AMOUNT - self.amount,
+            IntRange::Linear {
+                min: 0,
+                max: MAX_AMOUNT,
+            },
+        );
+        params.stereo_offset =
+            smoothed_param("Stereo Offset", ramp * 2400.0 - 1200.0, -1200.0, 1200.0);
+        params.mix = smoothed_param("Mix", 0.5 + ramp * 0.5, 0.0, 1.0);
+        params.amount_transition = EnumParam::new("Amount Transition", self.amount_transition);
+        params.oversampling = EnumParam::new("Oversampling", self.oversampling);
+        params.oversampling_filter =
+            EnumParam::new("Oversampling Filter", self.oversampling_filter);
+        params.stereo_mode = EnumParam::new("Stereo Mode", self.stereo_mode);
+        params.multiband.bands = IntParam::new(
+            "Bands",
+            self.bands,
+            IntRange::Linear {
+                min: 1,
+                max: MAX_BANDS as i32,
+            },
+        );
+        params.spring.enabled = BoolParam::new("Spring Mode", self.spring);
+        params.feedback.gain = smoothed_param("Feedback", self.feedback * ramp, -1.0, 1.0);
+        params.lfo.depth = smoothed_param("LFO Depth", self.lfo_depth, 0.0, 1.0);
+        params.envelope.source = EnumParam::new("Envelope Source", self.envelope_source);
+        params.envelope.target = EnumParam::new("Envelope Target", self.envelope_target);
+        params.envelope.depth =
+            smoothed_param("Envelope Depth", self.envelope_depth * ramp, -1.0, 1.0);
+        params.keytrack.enabled = BoolParam::new("Keytrack", self.keytrack);
+        params.zap.trigger = EnumParam::new("Zap Trigger", self.zap_trigger);
+        params.pitch.follow = BoolParam::new("Pitch Follow", self.pitch_follow);
+        params.transient.preserve = BoolParam::new("Transient Preserve", self.transient_preserve);
+        params.transient.amount = smoothed_param("Transient Amount", ramp, 0.0, 1.0);
+
+        for (param, previous) in smoothed_params(&params)
+            .into_iter()
+            .zip(smoothed_params(previous))
+        {
+            param.smoothed.reset(previous.smoothed.previous_value());
+            param.smoothed.set_target(SAMPLE_RATE, param.value());
+        }
+
+        Arc::new(params)
+    }
+}
+
+fn smoothed_param(name: &'static str, value: f32, min: f32, max: f32) -> FloatParam {
+    FloatParam::new(name, value, FloatRange::Linear { min, max })
+        .with_smoother(SmoothingStyle::Linear(20.0))
+}
+
+/// Every parameter the plugin reads through its smoother.
+fn smoothed_params(params: &DisperserParams) -> [&FloatParam; 10] {
+    [
+        &params.frequency,
+        &params.spread,
+        &params.stereo_offset,
+        &params.mix,
+        &params.input_gain,
+        &params.output_gain,
+        &params.lfo.depth,
+        &params.envelope.depth,
+        &params.transient.amount,
+        &params.feedback.gain,
+    ]
+}
+
+/// Stands in for the host. Nothing the plugin asks of it allocates.
+struct TestContext {
+    /// The current block's note events, last one first.
+    events: Vec<PluginNoteEvent<DisperserPlugin>>,
+}
+
+impl InitContext<DisperserPlugin> for TestContext {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Standalone
+    }
+
+    fn execute(&self, _task: ()) {}
+
+    fn set_latency_samples(&self, _samples: u32) {}
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
+
+impl ProcessContext<DisperserPlugin> for TestContext {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Standalone
+    }
+
+    fn execute_background(&self, _task: ()) {}
+
+    fn execute_gui(&self, _task: ()) {}
+
+    fn transport(&self) -> &Transport {
+        // Only the wrappers can create a transport, the tests pass its values to
+        // `process_block()` directly
+        unreachable!()
+    }
+
+    fn next_event(&mut self) -> Option<PluginNoteEvent<DisperserPlugin>> {
+        self.events.pop()
+    }
+
+    fn send_event(&mut self, _event: PluginNoteEvent<DisperserPlugin>) {}
+
+    fn set_latency_samples(&self, _samples: u32) {}
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
+
+/// One plugin instance with its own audio buffers.
+struct Harness {
+    plugin: DisperserPlugin,
+    context: TestContext,
+    main: Vec<Vec<f32>>,
+    sidechain: Vec<Vec<f32>>,
+    /// The number of samples processed so far.
+    time: usize,
+}
+
+impl Harness {
+    fn new(layout: &AudioIOLayout) -> Self {
+        let channels = |channels: Option<NonZeroU32>| channels.map(NonZeroU32::get).unwrap_or(0);
+        let main_channels = channels(layout.main_output_channels) as usize;
+        let sidechain_channels = layout
+            .aux_input_ports
+            .first()
+            .map(|channels| channels.get() as usize)
+            .unwrap_or(0);
+
+        let mut plugin = DisperserPlugin::default();
+        let mut context = TestContext {
+            events: Vec::with_capacity(BLOCK_SIZE),
+        };
+        let buffer_config = BufferConfig {
+            sample_rate: SAMPLE_RATE,
+            min_buffer_size: None,
+            max_buffer_size: BLOCK_SIZE as u32,
+            process_mode: ProcessMode::Realtime,
+        };
+        assert!(plugin.initialize(layout, &buffer_config, &mut context));
+        plugin.reset();
+
+        // Outside of a wrapper the smoothers start at zero instead of at the parameters' values
+        for param in smoothed_params(&plugin.params) {
+            param.smoothed.reset(param.value());
+        }
+
+        Self {
+            plugin,
+            context,
+            main: vec![vec![0.0; BLOCK_SIZE]; main_channels],
+            sidechain: vec![vec![0.0; BLOCK_SIZE]; sidechain_channels],
+            time: 0,
+        }
+    }
+
+    /// Switch to `settings` and process a block of the test signal, without allocating.
+    fn process(&mut self, settings: Settings) {
+        let block = self.time / BLOCK_SIZE;
+        let ramp = 1.0 - ((block % RAMP_BLOCKS) as f32 / RAMP_BLOCKS as f32 * 2.0 - 1.0).abs();
+        self.plugin.params = settings.params(&self.plugin.params, ramp);
+
+        if settings.pitch_learn && !self.plugin.pitch_learn.is_learning() {
+            self.plugin.pitch_learn.request();
+        }
+
+        self.context.events.clear();
+        self.context.events.extend(
+            (0..BLOCK_SIZE)
+                .rev()
+                .filter_map(|idx| note_event(self.time + idx, idx)),
+        );
+
+        let mut main = Buffer::default();
+        let mut sidechain = Buffer::default();
+        // SAFETY: The slices outlive the buffers, which are dropped at the end of this function
+        unsafe {
+            main.set_slices(BLOCK_SIZE, |slices| {
+                slices.clear();
+                slices.extend(self.main.iter_mut().map(Vec::as_mut_slice));
+            });
+            sidechain.set_slices(BLOCK_SIZE, |slices| {
+                slices.clear();
+                slices.extend(self.sidechain.iter_mut().map(Vec::as_mut_slice));
+            });
+        }
+        let mut aux_inputs = [sidechain];
+        let mut aux = AuxiliaryBuffers {
+            inputs: &mut aux_inputs,
+            outputs: &mut [],
+        };
+
+        let time = self.time;
+        let plugin = &mut self.plugin;
+        let context = &mut self.context;
+        assert_no_alloc(|| {
+            for (channel, samples) in main.as_slice().iter_mut().enumerate() {
+                for (idx, sample) in samples.iter_mut().enumerate() {
+                    *sample = test_signal(time + idx, channel);
+                }
+            }
+            // Half a burst behind the main input, so the sidechain's envelope has its own timing
+            for (channel, samples) in aux.inputs[0].as_slice().iter_mut().enumerate() {
+                for (idx, sample) in samples.iter_mut().enumerate() {
+                    *sample = test_signal(time + idx + NOTE_INTERVAL / 2, channel);
+                }
+            }
+
+            let tempo = Some(120.0);
+            let song_position = Some(time as f64 / SAMPLE_RATE as f64 * 2.0);
+            plugin.process_block(
+                &mut main,
+                &mut aux,
+                context,
+                tempo,
+                song_position,
+                settings.editor_open,
+            );
+        });
+
+        self.time += BLOCK_SIZE;
+    }
+
+    /// Sweep the amount from zero to its maximum and back with `settings`.
+    fn sweep_amount(&mut self, settings: Settings) {
+        let amounts = (0..=MAX_AMOUNT as usize)
+            .step_by(AMOUNT_STEP)
+            .chain((0..=MAX_AMOUNT as usize).rev().step_by(AMOUNT_STEP));
+        for amount in amounts {
+            self.process(Settings {
+                amount: amount as i32,
+                ..settings
+            });
+        }
+    }
+}
+
+/// A decaying burst every [`NOTE_INTERVAL`] samples, with a bit of a tone so the pitch detector
+/// has something to work with. Every channel gets a slightly different signal.
+fn test_signal(time: usize, channel: usize) -> f32 {
+    let burst = (time % NOTE_INTERVAL) as f32;
+    let tone = (time as f32 * (0.02 + channel as f32 * 0.003)).sin();
+    tone * (-burst / 200.0).exp() * 0.5
+}
+
+/// A note on at the start of every burst and a note off halfway through it, climbing by a
+/// semitone every time so the keytracker has something to glide between.
+fn note_event(time: usize, timing: usize) -> Option<PluginNoteEvent<DisperserPlugin>> {
+    let note = 36 + (time / NOTE_INTERVAL % 48) as u8;
+    match time % NOTE_INTERVAL {
+        0 => Some(NoteEvent::NoteOn {
+            timing: timing as u32,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 1.0,
+        }),
+        offset if offset == NOTE_INTERVAL / 2 => Some(NoteEvent::NoteOff {
+            timing: timing as u32,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 0.0,
+        }),
+        _ => None,
+    }
+}
+
+#[test]
+fn process_does_not_allocate() {
+    for layout in DisperserPlugin::AUDIO_IO_LAYOUTS {
+        let mut harness = Harness::new(layout);
+
+        for oversampling in [Oversampling::X1, Oversampling::X2, Oversampling::X4] {
+            for oversampling_filter in [
+                OversamplingFilter::MinimumPhase,
+                OversamplingFilter::LinearPhase,
+            ] {
+                for amount_transition in [AmountTransition::Jump, AmountTransition::Crossfade] {
+                    harness.sweep_amount(Settings {
+                        oversampling,
+                        oversampling_filter,
+                        amount_transition,
+                        ..Settings::default()
+                    });
+                }
+            }
+        }
+
+        for stereo_mode in [
+            StereoMode::LeftRight,
+            StereoMode::Mid,
+            StereoMode::Side,
+            StereoMode::MidSide,
+        ] {
+            for bands in 1..=MAX_BANDS as i32 {
+                for spring in [false, true] {
+                    for feedback in [0.0, 0.9, -0.9] {
+                        harness.sweep_amount(Settings {
+                            stereo_mode,
+                            bands,
+                            spring,
+                            feedback,
+                            oversampling: Oversampling::X2,
+                            ..Settings::default()
+                        });
+                    }
+                }
+            }
+        }
+
+        for envelope_source in [EnvelopeSource::Input, EnvelopeSource::Sidechain] {
+            for envelope_target in [
+                ModulationTarget::Frequency,
+                ModulationTarget::Spread,
+                ModulationTarget::Amount,
+            ] {
+                for zap_trigger in [ZapTrigger::Off, ZapTrigger::Midi, ZapTrigger::Transient] {
+                    for keytrack in [false, true] {
+                        harness.sweep_amount(Settings {
+                            lfo_depth: 1.0,
+                            envelope_source,
+                            envelope_target,
+                            envelope_depth: -1.0,
+                            keytrack,
+                            zap_trigger,
+                            ..Settings::default()
+                        });
+                    }
+                }
+            }
+        }
+
+        for pitch_follow in [false, true] {
+            for transient_preserve in [false, true] {
+                for editor_open in [false, true] {
+                    harness.sweep_amount(Settings {
+                        pitch_follow,
+                        transient_preserve,
+                        editor_open,
+                        bands: 2,
+                        oversampling: Oversampling::X2,
+                        ..Settings::default()
+                    });
+                }
+            }
+        }
+
+        // Learning only publishes its result once it has listened for a few seconds
+        for _ in 0..LEARN_BLOCKS {
+            harness.process(Settings {
+                amount: MAX_AMOUNT / 2,
+                pitch_learn: true,
+                editor_open: true,
+                ..Settings::default()
+            });
+        }
+    }
+}
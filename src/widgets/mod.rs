@@ -1,3 +1,7 @@
 pub mod params_knob;
 pub mod waveform_view;
 pub mod omg_peak_meter;
+pub mod sweep_view;
+pub mod response_view;
+pub mod spectrum_view;
+pub mod spectrogram_view;
@@ -2,11 +2,15 @@
 
 use nih_plug::prelude::util;
 use std::cell::Cell;
+use std::sync::Arc;
 use std::time::Duration;
 use std::time::Instant;
 use vizia_plug::vizia::prelude::*;
 use vizia_plug::vizia::vg;
 
+use crate::MAX_CHANNELS;
+use crate::dsp::meter::MeterLevels;
+
 /// The thickness of a tick inside of the peak meter's bar.
 const TICK_WIDTH: f32 = 1.0;
 /// The gap between individual ticks.
@@ -20,60 +24,45 @@ const MAX_TICK: f32 = 20.0;
 /// -infinity, and at the last position we'll draw the `dBFS` string.
 const TEXT_TICKS: [i32; 6] = [-80, -60, -40, -20, 0, 12];
 
-/// A simple horizontal peak meter.
+/// A simple horizontal peak meter with a bar for every channel. The peak level is drawn dimmed
+/// behind the RMS level.
 pub struct OmgPeakMeter;
 
 /// The bar bit for the peak meter, manually drawn using vertical lines.
-struct OmgPeakMeterBar<L, P>
+struct OmgPeakMeterBar<L, R, P>
 where
     L: Lens<Target = f32>,
+    R: Lens<Target = f32>,
     P: Lens<Target = f32>,
 {
     level_dbfs: L,
+    rms_dbfs: R,
     peak_dbfs: P,
 }
 
 impl OmgPeakMeter {
-    /// Creates a new [`PeakMeter`] for the given value in decibel, optionally holding the peak
-    /// value for a certain amount of time.
-    pub fn new<L>(cx: &mut Context, level_dbfs: L, hold_time: Option<Duration>) -> Handle<'_, Self>
+    /// Creates a new [`OmgPeakMeter`] for the given levels, optionally holding the peak value for
+    /// a certain amount of time. The number of bars follows [`MeterLevels::channels()`].
+    pub fn new<L>(cx: &mut Context, levels: L, hold_time: Option<Duration>) -> Handle<'_, Self>
     where
-        L: Lens<Target = f32>,
+        L: Lens<Target = Arc<MeterLevels>>,
     {
         Self.build(cx, |cx| {
-            // Now for something that may be illegal under some jurisdictions. If a hold time is
-            // given, then we'll build a new lens that always gives the held peak level for the
-            // current moment in time by mutating some values captured into the mapping closure.
-            let held_peak_value_db = Cell::new(f32::MIN);
-            let last_held_peak_value: Cell<Option<Instant>> = Cell::new(None);
-            let peak_dbfs = level_dbfs.map(move |level| -> f32 {
-                match hold_time {
-                    Some(hold_time) => {
-                        let mut peak_level = held_peak_value_db.get();
-                        let peak_time = last_held_peak_value.get();
-
-                        let now = Instant::now();
-                        if *level >= peak_level
-                            || peak_time.is_none()
-                            || now > peak_time.unwrap() + hold_time
-                        {
-                            peak_level = *level;
-                            held_peak_value_db.set(peak_level);
-                            last_held_peak_value.set(Some(now));
+            Binding::new(
+                cx,
+                levels.map(|levels| levels.channels()),
+                move |cx, channels| {
+                    let channels = channels.get(cx).clamp(1, MAX_CHANNELS);
+                    VStack::new(cx, |cx| {
+                        for channel in 0..channels {
+                            Self::bar(cx, levels, channel, hold_time);
                         }
-
-                        peak_level
-                    }
-                    None => util::MINUS_INFINITY_DB,
-                }
-            });
-
-            OmgPeakMeterBar {
-                level_dbfs,
-                peak_dbfs,
-            }
-            .build(cx, |_| {})
-            .class("bar");
+                    })
+                    .class("bars")
+                    // Surround layouts need every pixel of the meter's height for their bars
+                    .toggle_class("bars--surround", channels > 2);
+                },
+            );
 
             HStack::new(cx, |cx| {
                 for tick_db in TEXT_TICKS {
@@ -107,9 +96,56 @@ impl OmgPeakMeter {
             })
             .class("ticks");
         })
+    }
+
+    /// A single channel's bar.
+    fn bar<L>(cx: &mut Context, levels: L, channel: usize, hold_time: Option<Duration>)
+    where
+        L: Lens<Target = Arc<MeterLevels>>,
+    {
+        let level_dbfs = levels.map(move |levels| levels.peak_db(channel));
+        let rms_dbfs = levels.map(move |levels| levels.rms_db(channel));
+
+        // Now for something that may be illegal under some jurisdictions. If a hold time is
+        // given, then we'll build a new lens that always gives the held peak level for the
+        // current moment in time by mutating some values captured into the mapping closure.
+        let held_peak_value_db = Cell::new(f32::MIN);
+        let last_held_peak_value: Cell<Option<Instant>> = Cell::new(None);
+        let peak_dbfs = level_dbfs.map(move |level| -> f32 {
+            match hold_time {
+                Some(hold_time) => {
+                    let mut peak_level = held_peak_value_db.get();
+                    let peak_time = last_held_peak_value.get();
+
+                    let now = Instant::now();
+                    if *level >= peak_level
+                        || peak_time.is_none()
+                        || now > peak_time.unwrap() + hold_time
+                    {
+                        peak_level = *level;
+                        held_peak_value_db.set(peak_level);
+                        last_held_peak_value.set(Some(now));
+                    }
+
+                    peak_level
+                }
+                None => util::MINUS_INFINITY_DB,
+            }
+        });
+
+        OmgPeakMeterBar {
+            level_dbfs,
+            rms_dbfs,
+            peak_dbfs,
+        }
+        .build(cx, |_| {})
+        .class("bar")
         .bind(level_dbfs, |mut handle, _| {
             handle.needs_redraw();
         })
+        .bind(rms_dbfs, |mut handle, _| {
+            handle.needs_redraw();
+        });
     }
 }
 
@@ -119,13 +155,15 @@ impl View for OmgPeakMeter {
     }
 }
 
-impl<L, P> View for OmgPeakMeterBar<L, P>
+impl<L, R, P> View for OmgPeakMeterBar<L, R, P>
 where
     L: Lens<Target = f32>,
+    R: Lens<Target = f32>,
     P: Lens<Target = f32>,
 {
     fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
         let level_dbfs = self.level_dbfs.get(cx);
+        let rms_dbfs = self.rms_dbfs.get(cx);
         let peak_dbfs = self.peak_dbfs.get(cx);
 
         // These basics are taken directly from the default implementation of this function
@@ -182,7 +220,11 @@ where
             path.move_to((tick_x as f32 + (dpi_scale / 2.0), bar_bounds.top()));
             path.line_to((tick_x as f32 + (dpi_scale / 2.0), bar_bounds.bottom()));
 
-            let grayscale_color = 0.3 + ((1.0 - tick_fraction) * 0.5);
+            // Everything between the RMS and the peak level is only drawn faintly
+            let mut grayscale_color = 0.3 + ((1.0 - tick_fraction) * 0.5);
+            if tick_db > rms_dbfs {
+                grayscale_color *= 0.4;
+            }
             let mut paint = vg::Paint::default();
             paint.set_color4f(
                 vg::Color4f::new(177.0 / 255.0, 1.0, 192.0 / 255.0, grayscale_color),
@@ -29,11 +29,14 @@ pub struct ParamKnob {
     wheel_scalar: f32,
     centered: bool,
     dragging: bool,
+    /// The parameter's normalized value after modulation, if the plugin is modulating it.
+    modulated_value: Option<f32>,
 }
 
 enum ParamKnobEvent {
     CancelTextInput,
     TextInput(String),
+    SetModulatedValue(Option<f32>),
 }
 
 impl ParamKnob {
@@ -59,6 +62,7 @@ impl ParamKnob {
             wheel_scalar: DEFAULT_WHEEL_SCALAR,
             centered,
             dragging: false,
+            modulated_value: None,
         }
         .build(
             cx,
@@ -90,7 +94,9 @@ impl ParamKnob {
                             .width(Stretch(1.0))
                             .height(Stretch(1.0));
                     } else {
-                        ArcTrack::new(cx, is_centered, -150.0, 150.0).value(normalized_value_lens);
+                        ArcTrack::new(cx, is_centered, -150.0, 150.0)
+                            .value(normalized_value_lens)
+                            .modulated_value(ParamKnob::modulated_value);
                     }
                 });
 
@@ -130,6 +136,10 @@ impl View for ParamKnob {
                 cx.set_active(false);
                 meta.consume();
             }
+            ParamKnobEvent::SetModulatedValue(value) => {
+                self.modulated_value = *value;
+                meta.consume();
+            }
         });
 
         event.map(|window_event, meta| match window_event {
@@ -257,14 +267,36 @@ impl View for ParamKnob {
     }
 }
 
+pub trait ParamKnobExt {
+    /// Show a second marker on the knob's ring for the parameter's modulated normalized value.
+    /// Negative values hide the marker.
+    fn modulated_value<L: Lens<Target = f32>>(self, lens: L) -> Self;
+}
+
+impl ParamKnobExt for Handle<'_, ParamKnob> {
+    fn modulated_value<L: Lens<Target = f32>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, value| {
+            let value = value.get(cx);
+            cx.emit_to(
+                entity,
+                ParamKnobEvent::SetModulatedValue((value >= 0.0).then_some(value)),
+            );
+        });
+        self
+    }
+}
+
 pub enum ArcTrackEvent {
     SetValue(f32),
+    SetModulatedValue(Option<f32>),
 }
 
 pub struct ArcTrack {
     angle_start: f32,
     angle_end: f32,
     normalized_value: f32,
+    modulated_value: Option<f32>,
     center: bool,
 }
 
@@ -279,6 +311,7 @@ impl ArcTrack {
             angle_start,
             angle_end,
             normalized_value: 0.0,
+            modulated_value: None,
             center,
         }
         .build(cx, |_| {})
@@ -287,6 +320,7 @@ impl ArcTrack {
 
 pub trait ArcTrackHandle {
     fn value<L: Lens<Target = f32>>(self, lens: L) -> Self;
+    fn modulated_value<L: Lens<Target = Option<f32>>>(self, lens: L) -> Self;
 }
 
 impl ArcTrackHandle for Handle<'_, ArcTrack> {
@@ -297,6 +331,14 @@ impl ArcTrackHandle for Handle<'_, ArcTrack> {
         });
         self
     }
+
+    fn modulated_value<L: Lens<Target = Option<f32>>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, value| {
+            cx.emit_to(entity, ArcTrackEvent::SetModulatedValue(value.get(cx)));
+        });
+        self
+    }
 }
 
 impl View for ArcTrack {
@@ -310,6 +352,10 @@ impl View for ArcTrack {
                 self.normalized_value = *val;
                 cx.needs_redraw();
             }
+            ArcTrackEvent::SetModulatedValue(val) => {
+                self.modulated_value = *val;
+                cx.needs_redraw();
+            }
         });
     }
 
@@ -403,6 +449,45 @@ impl View for ArcTrack {
         path.move_to((tick_x0, tick_y0));
         path.line_to((tick_x1, tick_y1));
         canvas.draw_path(&path, &paint_tick);
+
+        // draw modulation, a thin arc from the set value to the modulated value and a dot on it
+        if let Some(modulated_value) = self.modulated_value {
+            let modulated_value = modulated_value.clamp(0.0, 1.0);
+            let modulation_radius = draw_radius - stroke_width;
+
+            let modulation_oval = vg::Rect::new(
+                center_x - modulation_radius,
+                center_y - modulation_radius,
+                center_x + modulation_radius,
+                center_y + modulation_radius,
+            );
+
+            let mut paint_mod = vg::Paint::default();
+            paint_mod.set_color(Color::rgba(0, 160, 40, 160));
+            paint_mod.set_stroke_width(stroke_width * 0.5);
+            paint_mod.set_stroke_cap(vg::PaintCap::Round);
+            paint_mod.set_style(vg::PaintStyle::Stroke);
+            paint_mod.set_anti_alias(true);
+            canvas.draw_arc(
+                &modulation_oval,
+                current_angle_deg,
+                (modulated_value - value) * sweep_angle_deg,
+                false,
+                &paint_mod,
+            );
+
+            let modulated_angle_rad =
+                (start_angle_deg + modulated_value * sweep_angle_deg).to_radians();
+            paint_mod.set_style(vg::PaintStyle::Fill);
+            canvas.draw_circle(
+                Point::new(
+                    center_x + modulated_angle_rad.cos() * modulation_radius,
+                    center_y + modulated_angle_rad.sin() * modulation_radius,
+                ),
+                stroke_width * 0.6,
+                &paint_mod,
+            );
+        }
     }
 }
 
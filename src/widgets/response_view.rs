@@ -0,0 +1,517 @@
+use nih_plug::prelude::Param;
+use std::sync::Arc;
+use vizia_plug::vizia::{prelude::*, vg};
+use vizia_plug::widgets::param_base::ParamWidgetBase;
+use vizia_plug::widgets::util::ModifiersExt;
+
+use crate::dsp::multiband::MAX_BANDS;
+use crate::dsp::response::{CascadeResponse, cascade_response};
+use crate::{DisperserParams, MAX_AMOUNT, MAX_FREQUENCY, MAX_SPREAD, MIN_FREQUENCY, MIN_SPREAD};
+
+/// The number of line segments used to draw the curves.
+const CURVE_RESOLUTION: usize = 256;
+/// The group delay axis never gets shorter than this, so a cascade with barely any stages doesn't
+/// get blown up to fill the whole view.
+const MIN_DELAY_RANGE_MS: f32 = 1.0;
+/// Grid lines are drawn at these frequencies.
+const GRID_FREQUENCIES: [f32; 3] = [100.0, 1000.0, 10000.0];
+/// The radius of the handle drawn on the curve's peak.
+const HANDLE_RADIUS: f32 = 4.0;
+
+/// Dragging over the full height of the view changes the spread by this many octaves.
+const DRAG_SPREAD_OCTAVES: f32 = 6.0;
+/// Dragging this many pixels changes the amount by one stage.
+const DRAG_AMOUNT_PIXELS: f32 = 2.0;
+/// A single scroll step changes the spread by this many octaves.
+const SCROLL_SPREAD_OCTAVES: f32 = 1.0 / 6.0;
+/// Holding shift makes dragging and scrolling this much finer.
+const FINE_SCALAR: f32 = 0.1;
+
+pub enum ResponseViewEvent {
+    SetFrequency(f32),
+    SetSpread(f32),
+    SetAmount(usize),
+    SetSampleRate(f32),
+    SetLayout(Layout),
+}
+
+/// Which cascades make up the signal path, and so what the graph needs to show.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    /// The plain cascade, with the modulated frequency, spread and amount.
+    Plain,
+    /// Every band's own cascade over its part of the spectrum. The crossovers' phase shift isn't
+    /// included.
+    Multiband {
+        bands: [BandCascade; MAX_BANDS],
+        num_bands: usize,
+    },
+    /// A single trip through the spring tank's first cascade. The delays and the feedback around
+    /// it aren't included, so this is only an approximation and it's drawn dashed.
+    Spring { frequency: f32 },
+}
+
+/// A single band's cascade, and the part of the spectrum it covers.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BandCascade {
+    frequency: f32,
+    spread: f32,
+    amount: usize,
+    /// The crossover at the top of this band, or infinity for the highest band.
+    upper_crossover: f32,
+}
+
+impl Data for Layout {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl Layout {
+    fn new(params: &DisperserParams) -> Self {
+        // The spring tank takes the place of both the plain and the multiband cascades
+        if params.spring.enabled.value() {
+            return Layout::Spring {
+                frequency: params.spring.frequency(),
+            };
+        }
+
+        let multiband = &params.multiband;
+        let num_bands = multiband.bands.value() as usize;
+        if num_bands == 1 {
+            return Layout::Plain;
+        }
+
+        let mut crossovers = multiband.crossovers();
+        let crossovers = &mut crossovers[..num_bands - 1];
+        crossovers.sort_by(f32::total_cmp);
+
+        let mut bands = [BandCascade::default(); MAX_BANDS];
+        for (idx, (band, params)) in bands
+            .iter_mut()
+            .zip(&multiband.band[..num_bands])
+            .enumerate()
+        {
+            *band = BandCascade {
+                frequency: params.frequency.value(),
+                spread: params.spread.value(),
+                amount: params.amount.value() as usize,
+                upper_crossover: crossovers.get(idx).copied().unwrap_or(f32::INFINITY),
+            };
+        }
+
+        Layout::Multiband { bands, num_bands }
+    }
+}
+
+/// What vertical movement changes while dragging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DragTarget {
+    Spread,
+    Amount,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DragStatus {
+    target: DragTarget,
+    start_y: f32,
+    start_spread: f32,
+    start_amount: i32,
+    /// The fine scalar the drag is currently using. The drag starts over from the current
+    /// position whenever it changes, so pressing or releasing shift doesn't make the value jump.
+    fine: f32,
+}
+
+impl DragStatus {
+    /// The spread and amount for the cursor at `y`, in a view that's `height` pixels tall.
+    fn values_at(&self, y: f32, height: f32) -> (f32, i32) {
+        let dy = (self.start_y - y) * self.fine;
+        let spread = self.start_spread * 2.0f32.powf(dy / height * DRAG_SPREAD_OCTAVES);
+        let amount = self.start_amount + (dy / DRAG_AMOUNT_PIXELS).round() as i32;
+
+        (
+            spread.clamp(MIN_SPREAD, MAX_SPREAD),
+            amount.clamp(0, MAX_AMOUNT),
+        )
+    }
+}
+
+/// Draws the cascade's group delay over a log frequency axis, with the wrapped phase response
+/// faintly behind it. Dragging moves the peak: horizontally to set the frequency, vertically or
+/// by scrolling to set the spread. With the command key held, vertical movement and scrolling
+/// change the amount instead. With multiple bands or in spring mode those cascades are drawn
+/// instead, see [`Layout`].
+pub struct ResponseView {
+    frequency: f32,
+    spread: f32,
+    amount: usize,
+    sample_rate: f32,
+    layout: Layout,
+
+    params: Arc<DisperserParams>,
+    frequency_param: ParamWidgetBase,
+    spread_param: ParamWidgetBase,
+    amount_param: ParamWidgetBase,
+    drag_status: Option<DragStatus>,
+    /// Scrolling changes the amount in whole stages, finer scrolling needs a few steps for one.
+    amount_scroll: f32,
+}
+
+impl ResponseView {
+    /// `frequency`, `spread` and `amount` are what gets drawn, `params` is what dragging changes.
+    pub fn new<L, L1, L2, L3, L4>(
+        cx: &mut Context,
+        params: L,
+        frequency: L1,
+        spread: L2,
+        amount: L3,
+        sample_rate: L4,
+    ) -> Handle<'_, Self>
+    where
+        L: Lens<Target = Arc<DisperserParams>> + Clone,
+        L1: Lens<Target = f32>,
+        L2: Lens<Target = f32>,
+        L3: Lens<Target = usize>,
+        L4: Lens<Target = f32>,
+    {
+        let layout = params
+            .clone()
+            .map(|params: &Arc<DisperserParams>| Layout::new(params));
+
+        Self {
+            frequency: 1000.0,
+            spread: 100.0,
+            amount: 0,
+            sample_rate: 44100.0,
+            layout: Layout::Plain,
+
+            params: params.get(cx),
+            frequency_param: ParamWidgetBase::new(cx, params.clone(), |params| &params.frequency),
+            spread_param: ParamWidgetBase::new(cx, params.clone(), |params| &params.spread),
+            amount_param: ParamWidgetBase::new(cx, params, |params| &params.amount),
+            drag_status: None,
+            amount_scroll: 0.0,
+        }
+        .build(cx, |cx| {
+            Binding::new(cx, frequency, |cx, value| {
+                let value = value.get(cx);
+                cx.emit(ResponseViewEvent::SetFrequency(value));
+            });
+            Binding::new(cx, spread, |cx, value| {
+                let value = value.get(cx);
+                cx.emit(ResponseViewEvent::SetSpread(value));
+            });
+            Binding::new(cx, amount, |cx, value| {
+                let value = value.get(cx);
+                cx.emit(ResponseViewEvent::SetAmount(value));
+            });
+            Binding::new(cx, sample_rate, |cx, value| {
+                let value = value.get(cx);
+                cx.emit(ResponseViewEvent::SetSampleRate(value));
+            });
+            Binding::new(cx, layout, |cx, value| {
+                let value = value.get(cx);
+                cx.emit(ResponseViewEvent::SetLayout(value));
+            });
+        })
+    }
+}
+
+impl View for ResponseView {
+    fn element(&self) -> Option<&'static str> {
+        Some("response-view")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|response_event, _| {
+            match response_event {
+                ResponseViewEvent::SetFrequency(value) => self.frequency = *value,
+                ResponseViewEvent::SetSpread(value) => self.spread = *value,
+                ResponseViewEvent::SetAmount(value) => self.amount = *value,
+                ResponseViewEvent::SetSampleRate(value) => self.sample_rate = *value,
+                ResponseViewEvent::SetLayout(value) => self.layout = *value,
+            }
+            cx.needs_redraw();
+        });
+
+        event.map(|window_event, meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                let target = if cx.modifiers().command() {
+                    DragTarget::Amount
+                } else {
+                    DragTarget::Spread
+                };
+
+                cx.capture();
+                cx.set_active(true);
+                self.frequency_param.begin_set_parameter(cx);
+                self.drag_param(target).begin_set_parameter(cx);
+                self.drag_status = Some(DragStatus {
+                    target,
+                    start_y: cx.mouse().cursor_y,
+                    start_spread: self.params.spread.unmodulated_plain_value(),
+                    start_amount: self.params.amount.unmodulated_plain_value(),
+                    fine: fine_scalar(cx),
+                });
+
+                // The peak jumps to the cursor right away, so it can be grabbed anywhere
+                self.drag_to(cx, cx.mouse().cursor_x, cx.mouse().cursor_y);
+                meta.consume();
+            }
+
+            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                // The first click already started a drag, which needs to end before the reset
+                self.end_drag(cx);
+                for param in [&self.frequency_param, &self.spread_param] {
+                    param.begin_set_parameter(cx);
+                    param.set_normalized_value(cx, param.default_normalized_value());
+                    param.end_set_parameter(cx);
+                }
+                meta.consume();
+            }
+
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.end_drag(cx) {
+                    meta.consume();
+                }
+            }
+
+            WindowEvent::MouseMove(x, y) => {
+                if self.drag_status.is_some() {
+                    self.drag_to(cx, *x, *y);
+                    meta.consume();
+                }
+            }
+
+            WindowEvent::MouseScroll(_, y) => {
+                if *y != 0.0 && self.drag_status.is_none() {
+                    let fine = fine_scalar(cx);
+                    if cx.modifiers().command() {
+                        self.amount_scroll += y * fine;
+                        let steps = self.amount_scroll.trunc();
+                        self.amount_scroll -= steps;
+                        if steps != 0.0 {
+                            let amount =
+                                self.params.amount.unmodulated_plain_value() + steps as i32;
+                            let normalized = self.params.amount.preview_normalized(amount);
+                            set_gesture(cx, &self.amount_param, normalized);
+                        }
+                    } else {
+                        let spread = self.params.spread.unmodulated_plain_value()
+                            * 2.0f32.powf(y * SCROLL_SPREAD_OCTAVES * fine);
+                        let normalized = self.params.spread.preview_normalized(spread);
+                        set_gesture(cx, &self.spread_param, normalized);
+                    }
+                    meta.consume();
+                }
+            }
+
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let background_color = cx.background_color();
+        let stroke_color = cx.font_color();
+
+        let mut bg_paint = vg::Paint::default();
+        bg_paint.set_color(background_color);
+        let rect = vg::Rect::from_xywh(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.draw_rect(&rect, &bg_paint);
+
+        let log_range = (MAX_FREQUENCY / MIN_FREQUENCY).ln();
+        let frequency_to_x =
+            |frequency: f32| bounds.x + (frequency / MIN_FREQUENCY).ln() / log_range * bounds.w;
+
+        let mut grid_path = vg::Path::new();
+        for frequency in GRID_FREQUENCIES {
+            let x = frequency_to_x(frequency);
+            grid_path.move_to((x, bounds.y));
+            grid_path.line_to((x, bounds.y + bounds.h));
+        }
+        let mut grid_paint = vg::Paint::default();
+        grid_paint.set_color(stroke_color);
+        grid_paint.set_alpha_f(0.15);
+        grid_paint.set_stroke_width(1.0);
+        grid_paint.set_style(vg::PaintStyle::Stroke);
+        canvas.draw_path(&grid_path, &grid_paint);
+
+        let responses: Vec<_> = (0..=CURVE_RESOLUTION)
+            .map(|i| {
+                let t = i as f32 / CURVE_RESOLUTION as f32;
+                let frequency = MIN_FREQUENCY * (log_range * t).exp();
+                (bounds.x + t * bounds.w, self.response(frequency))
+            })
+            .collect();
+
+        let max_delay_ms = responses
+            .iter()
+            .map(|(_, response)| response.group_delay * 1000.0)
+            .fold(MIN_DELAY_RANGE_MS, f32::max);
+
+        // The phase is wrapped to a single turn so it stays readable with hundreds of stages
+        let mut phase_path = vg::Path::new();
+        let mut delay_path = vg::Path::new();
+        let mut last_wrapped_phase = None;
+        for (i, (x, response)) in responses.iter().enumerate() {
+            let wrapped_phase = response.phase.rem_euclid(std::f32::consts::TAU);
+            let phase_y = bounds.y + (1.0 - wrapped_phase / std::f32::consts::TAU) * bounds.h;
+            match last_wrapped_phase {
+                Some(last) if wrapped_phase <= last => phase_path.line_to((*x, phase_y)),
+                _ => phase_path.move_to((*x, phase_y)),
+            };
+            last_wrapped_phase = Some(wrapped_phase);
+
+            let delay_ms = response.group_delay * 1000.0;
+            let delay_y =
+                bounds.y + bounds.h - (delay_ms / max_delay_ms).clamp(0.0, 1.0) * bounds.h;
+            if i == 0 {
+                delay_path.move_to((*x, delay_y));
+            } else {
+                delay_path.line_to((*x, delay_y));
+            }
+        }
+
+        let mut phase_paint = vg::Paint::default();
+        phase_paint.set_color(stroke_color);
+        phase_paint.set_alpha_f(0.3);
+        phase_paint.set_stroke_width(1.0);
+        phase_paint.set_style(vg::PaintStyle::Stroke);
+        phase_paint.set_anti_alias(true);
+        canvas.draw_path(&phase_path, &phase_paint);
+
+        let mut stroke_paint = vg::Paint::default();
+        stroke_paint.set_color(stroke_color);
+        stroke_paint.set_stroke_width(cx.border_width().max(1.5));
+        stroke_paint.set_style(vg::PaintStyle::Stroke);
+        stroke_paint.set_stroke_cap(vg::PaintCap::Round);
+        stroke_paint.set_stroke_join(vg::PaintJoin::Round);
+        stroke_paint.set_anti_alias(true);
+        if let Layout::Spring { .. } = self.layout {
+            stroke_paint.set_path_effect(vg::PathEffect::dash(&[4.0, 4.0], 0.0));
+        }
+        canvas.draw_path(&delay_path, &stroke_paint);
+
+        // Dragging only moves the plain cascade, so the handle is only shown while that's the one
+        // being drawn
+        if self.layout != Layout::Plain {
+            return;
+        }
+
+        // The handle sits on top of the peak, which is always at the centre frequency
+        let peak = cascade_response(
+            self.frequency,
+            self.spread,
+            self.amount,
+            self.sample_rate,
+            self.frequency,
+        );
+        let handle_x = frequency_to_x(self.frequency.clamp(MIN_FREQUENCY, MAX_FREQUENCY));
+        let handle_y = bounds.y + bounds.h
+            - (peak.group_delay * 1000.0 / max_delay_ms).clamp(0.0, 1.0) * bounds.h;
+        let mut handle_paint = vg::Paint::default();
+        handle_paint.set_color(stroke_color);
+        handle_paint.set_style(vg::PaintStyle::Fill);
+        handle_paint.set_anti_alias(true);
+        canvas.draw_circle(
+            vg::Point::new(handle_x, handle_y.max(bounds.y + HANDLE_RADIUS)),
+            HANDLE_RADIUS,
+            &handle_paint,
+        );
+    }
+}
+
+impl ResponseView {
+    /// The response of whichever cascade processes `frequency`.
+    fn response(&self, frequency: f32) -> CascadeResponse {
+        let (centre, spread, amount) = match self.layout {
+            Layout::Plain => (self.frequency, self.spread, self.amount),
+            Layout::Multiband { bands, num_bands } => {
+                // The highest band's crossover is at infinity, so this always finds a band
+                let band = bands[..num_bands]
+                    .iter()
+                    .find(|band| frequency < band.upper_crossover)
+                    .unwrap_or(&bands[num_bands - 1]);
+                (band.frequency, band.spread, band.amount)
+            }
+            Layout::Spring { frequency } => (frequency, self.spread, self.amount),
+        };
+
+        cascade_response(centre, spread, amount, self.sample_rate, frequency)
+    }
+
+    fn drag_param(&self, target: DragTarget) -> &ParamWidgetBase {
+        match target {
+            DragTarget::Spread => &self.spread_param,
+            DragTarget::Amount => &self.amount_param,
+        }
+    }
+
+    /// End the current drag's gestures. Returns whether there was a drag to end.
+    fn end_drag(&mut self, cx: &mut EventContext) -> bool {
+        let Some(status) = self.drag_status.take() else {
+            return false;
+        };
+
+        cx.release();
+        cx.set_active(false);
+        self.frequency_param.end_set_parameter(cx);
+        self.drag_param(status.target).end_set_parameter(cx);
+        true
+    }
+
+    /// Move the peak to follow the cursor during a drag.
+    fn drag_to(&mut self, cx: &mut EventContext, x: f32, y: f32) {
+        let Some(status) = &mut self.drag_status else {
+            return;
+        };
+
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        // The horizontal axis is the same log scale the curve is drawn on
+        let position = ((x - bounds.x) / bounds.w).clamp(0.0, 1.0);
+        let frequency = MIN_FREQUENCY * (MAX_FREQUENCY / MIN_FREQUENCY).powf(position);
+        self.frequency_param
+            .set_normalized_value(cx, self.params.frequency.preview_normalized(frequency));
+
+        let fine = fine_scalar(cx);
+        if fine != status.fine {
+            (status.start_spread, status.start_amount) = status.values_at(y, bounds.h);
+            status.start_y = y;
+            status.fine = fine;
+        }
+
+        let (spread, amount) = status.values_at(y, bounds.h);
+        match status.target {
+            DragTarget::Spread => self
+                .spread_param
+                .set_normalized_value(cx, self.params.spread.preview_normalized(spread)),
+            DragTarget::Amount => self
+                .amount_param
+                .set_normalized_value(cx, self.params.amount.preview_normalized(amount)),
+        }
+    }
+}
+
+fn fine_scalar(cx: &EventContext) -> f32 {
+    if cx.modifiers().shift() {
+        FINE_SCALAR
+    } else {
+        1.0
+    }
+}
+
+/// Change a parameter as a single gesture.
+fn set_gesture(cx: &mut EventContext, param: &ParamWidgetBase, normalized: f32) {
+    param.begin_set_parameter(cx);
+    param.set_normalized_value(cx, normalized);
+    param.end_set_parameter(cx);
+}
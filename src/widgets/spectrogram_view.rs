@@ -0,0 +1,446 @@
+use nih_plug::util;
+use realfft::num_complex::Complex32;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::sync::Arc;
+use vizia_plug::vizia::{prelude::*, vg};
+
+use crate::dsp::analyzer::{AnalyzerTaps, RING_CAPACITY, SampleRing};
+use crate::{MAX_FREQUENCY, MIN_FREQUENCY};
+
+/// The number of spectra kept and drawn, the newest on the right.
+const COLUMNS: usize = 256;
+/// The number of log spaced frequency bands each spectrum is reduced to.
+const ROWS: usize = 128;
+/// Spectra are computed every `fft_size / HOP_DIVISOR` samples.
+const HOP_DIVISOR: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum SpectrogramFftSize {
+    Fft1024,
+    Fft2048,
+    Fft4096,
+}
+
+impl SpectrogramFftSize {
+    pub fn label(self) -> &'static str {
+        match self {
+            SpectrogramFftSize::Fft1024 => "FFT 1024",
+            SpectrogramFftSize::Fft2048 => "FFT 2048",
+            SpectrogramFftSize::Fft4096 => "FFT 4096",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            SpectrogramFftSize::Fft1024 => SpectrogramFftSize::Fft2048,
+            SpectrogramFftSize::Fft2048 => SpectrogramFftSize::Fft4096,
+            SpectrogramFftSize::Fft4096 => SpectrogramFftSize::Fft1024,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            SpectrogramFftSize::Fft1024 => 1024,
+            SpectrogramFftSize::Fft2048 => 2048,
+            SpectrogramFftSize::Fft4096 => 4096,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum SpectrogramColourMap {
+    /// Black to the editor's green.
+    Green,
+    /// Black through red and yellow to white.
+    Heat,
+    Grey,
+}
+
+impl SpectrogramColourMap {
+    pub fn label(self) -> &'static str {
+        match self {
+            SpectrogramColourMap::Green => "GREEN",
+            SpectrogramColourMap::Heat => "HEAT",
+            SpectrogramColourMap::Grey => "GREY",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            SpectrogramColourMap::Green => SpectrogramColourMap::Heat,
+            SpectrogramColourMap::Heat => SpectrogramColourMap::Grey,
+            SpectrogramColourMap::Grey => SpectrogramColourMap::Green,
+        }
+    }
+
+    /// The colour for a level, with 0 at the bottom of the dB range and 1 at the top.
+    fn colour(self, t: f32) -> vg::Color4f {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            SpectrogramColourMap::Green => {
+                vg::Color4f::new(t * 152.0 / 255.0, t * 251.0 / 255.0, t * 152.0 / 255.0, 1.0)
+            }
+            SpectrogramColourMap::Heat => vg::Color4f::new(
+                (t * 3.0).min(1.0),
+                (t * 3.0 - 1.0).clamp(0.0, 1.0),
+                (t * 3.0 - 2.0).clamp(0.0, 1.0),
+                1.0,
+            ),
+            SpectrogramColourMap::Grey => vg::Color4f::new(t, t, t, 1.0),
+        }
+    }
+}
+
+/// How far below 0 dBFS the colour map reaches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum SpectrogramRange {
+    Db60,
+    Db80,
+    Db100,
+}
+
+impl SpectrogramRange {
+    pub fn label(self) -> &'static str {
+        match self {
+            SpectrogramRange::Db60 => "60 dB",
+            SpectrogramRange::Db80 => "80 dB",
+            SpectrogramRange::Db100 => "100 dB",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            SpectrogramRange::Db60 => SpectrogramRange::Db80,
+            SpectrogramRange::Db80 => SpectrogramRange::Db100,
+            SpectrogramRange::Db100 => SpectrogramRange::Db60,
+        }
+    }
+
+    fn floor_db(self) -> f32 {
+        match self {
+            SpectrogramRange::Db60 => -60.0,
+            SpectrogramRange::Db80 => -80.0,
+            SpectrogramRange::Db100 => -100.0,
+        }
+    }
+}
+
+enum SpectrogramViewEvent {
+    /// The audio thread wrote new samples.
+    Update,
+    SetSampleRate(f32),
+    SetFftSize(SpectrogramFftSize),
+    SetColourMap(SpectrogramColourMap),
+    SetRange(SpectrogramRange),
+    /// Show the output instead of the input.
+    SetShowOutput(bool),
+}
+
+/// A scrolling spectrogram of either the input or the output. Time runs from left to right and
+/// frequency upwards on a log scale, so the cascade's frequency dependent delay shows up as the
+/// sweep it turns a transient into.
+pub struct SpectrogramView {
+    taps: Arc<AnalyzerTaps>,
+    sample_rate: f32,
+    colour_map: SpectrogramColourMap,
+    range: SpectrogramRange,
+    show_output: bool,
+
+    fft: Arc<dyn RealToComplex<f32>>,
+    window: Vec<f32>,
+    /// The power of a full scale sine, so those end up at 0 dBFS.
+    full_scale_power: f32,
+    /// Scratch space for the FFT.
+    samples: Vec<f32>,
+    spectrum: Vec<Complex32>,
+    /// The total sample count the next spectrum ends at.
+    next_hop: usize,
+
+    /// `COLUMNS` spectra of `ROWS` levels in dB, as a ring. `column` is the oldest one.
+    levels: Vec<f32>,
+    column: usize,
+    /// The levels after the colour map, as `COLUMNS` by `ROWS` RGBA pixels with the highest
+    /// frequency at the top. The columns are in the same ring order as `levels`.
+    pixels: Vec<u8>,
+    /// `pixels` as an image, rebuilt whenever they change.
+    image: Option<vg::Image>,
+}
+
+impl SpectrogramView {
+    pub fn new<L1, L2>(cx: &mut Context, taps: L1, sample_rate: L2) -> Handle<'_, Self>
+    where
+        L1: Lens<Target = Arc<AnalyzerTaps>>,
+        L2: Lens<Target = f32>,
+    {
+        let fft_size = SpectrogramFftSize::Fft2048.size();
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(fft_size);
+        let window = util::window::hann(fft_size);
+
+        let mut view = Self {
+            taps: taps.get(cx),
+            sample_rate: 44100.0,
+            colour_map: SpectrogramColourMap::Green,
+            range: SpectrogramRange::Db80,
+            show_output: true,
+
+            samples: fft.make_input_vec(),
+            spectrum: fft.make_output_vec(),
+            full_scale_power: full_scale_power(&window),
+            fft,
+            window,
+            next_hop: 0,
+
+            levels: vec![util::MINUS_INFINITY_DB; COLUMNS * ROWS],
+            column: 0,
+            pixels: vec![0; COLUMNS * ROWS * 4],
+            image: None,
+        };
+        view.recolour();
+
+        view.build(cx, |cx| {
+            Binding::new(cx, taps.map(|taps| taps.output.written()), |cx, _| {
+                cx.emit(SpectrogramViewEvent::Update);
+            });
+            Binding::new(cx, sample_rate, |cx, value| {
+                let value = value.get(cx);
+                cx.emit(SpectrogramViewEvent::SetSampleRate(value));
+            });
+        })
+    }
+
+    fn set_fft_size(&mut self, fft_size: SpectrogramFftSize) {
+        let fft_size = fft_size.size();
+        if fft_size == self.window.len() {
+            return;
+        }
+
+        self.fft = RealFftPlanner::<f32>::new().plan_fft_forward(fft_size);
+        self.window = util::window::hann(fft_size);
+        self.full_scale_power = full_scale_power(&self.window);
+        self.samples = self.fft.make_input_vec();
+        self.spectrum = self.fft.make_output_vec();
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        self.levels.fill(util::MINUS_INFINITY_DB);
+        self.next_hop = 0;
+        self.recolour();
+    }
+
+    /// Redo every column's pixels, for when the colour map or range changes.
+    fn recolour(&mut self) {
+        for column in 0..COLUMNS {
+            self.colour_column(column);
+        }
+        self.update_image();
+    }
+
+    /// Write a column's levels to `pixels`.
+    fn colour_column(&mut self, column: usize) {
+        let floor_db = self.range.floor_db();
+        for (row, level) in self.levels[column * ROWS..(column + 1) * ROWS]
+            .iter()
+            .enumerate()
+        {
+            let colour = self
+                .colour_map
+                .colour((level - floor_db) / -floor_db)
+                .to_color();
+            let idx = ((ROWS - 1 - row) * COLUMNS + column) * 4;
+            self.pixels[idx..idx + 4].copy_from_slice(&[colour.r(), colour.g(), colour.b(), 255]);
+        }
+    }
+
+    fn update_image(&mut self) {
+        let info = vg::ImageInfo::new(
+            (COLUMNS as i32, ROWS as i32),
+            vg::ColorType::RGBA8888,
+            vg::AlphaType::Opaque,
+            None,
+        );
+        self.image =
+            vg::images::raster_from_data(&info, vg::Data::new_copy(&self.pixels), COLUMNS * 4);
+    }
+
+    /// Compute a spectrum for every hop the audio thread has completed since the last update.
+    fn update(&mut self) {
+        let taps = self.taps.clone();
+        let ring = if self.show_output {
+            &taps.output
+        } else {
+            &taps.input
+        };
+
+        let fft_size = self.window.len();
+        let hop = fft_size / HOP_DIVISOR;
+        let written = ring.written();
+
+        // After a long pause, or the first time around, only the most recent hops are worth
+        // drawing, and older samples may already be overwritten
+        let oldest_readable = written.saturating_sub(RING_CAPACITY - fft_size);
+        let oldest_drawn = written.saturating_sub(COLUMNS * hop);
+        let oldest = oldest_readable.max(oldest_drawn).max(fft_size);
+        if self.next_hop < oldest || self.next_hop > written + hop {
+            self.next_hop = (written - written % hop).max(fft_size);
+        }
+
+        let mut changed = false;
+        while self.next_hop <= written {
+            self.analyze(ring, self.next_hop);
+            self.next_hop += hop;
+            changed = true;
+        }
+        if changed {
+            self.update_image();
+        }
+    }
+
+    /// Add the spectrum of the samples leading up to `end` as the newest column.
+    fn analyze(&mut self, ring: &SampleRing, end: usize) {
+        ring.read_until(end, &mut self.samples);
+        for (sample, window) in self.samples.iter_mut().zip(&self.window) {
+            *sample *= window;
+        }
+        if self
+            .fft
+            .process(&mut self.samples, &mut self.spectrum)
+            .is_err()
+        {
+            return;
+        }
+
+        let bin_width = self.sample_rate / self.window.len() as f32;
+        let last_bin = self.spectrum.len() - 1;
+        let log_range = (MAX_FREQUENCY / MIN_FREQUENCY).ln();
+        let column = &mut self.levels[self.column * ROWS..(self.column + 1) * ROWS];
+        for (row, level) in column.iter_mut().enumerate() {
+            // Every row covers a slice of the log frequency axis. The low rows are narrower than
+            // a bin, so they get at least the one bin they fall in.
+            let low = MIN_FREQUENCY * (log_range * row as f32 / ROWS as f32).exp();
+            let high = MIN_FREQUENCY * (log_range * (row + 1) as f32 / ROWS as f32).exp();
+            let first_bin = ((low / bin_width).round() as usize).min(last_bin);
+            let end_bin = ((high / bin_width).round() as usize).clamp(first_bin + 1, last_bin + 1);
+            let power = self.spectrum[first_bin..end_bin]
+                .iter()
+                .map(|bin| bin.norm_sqr())
+                .fold(0.0, f32::max);
+
+            *level = util::gain_to_db((power / self.full_scale_power).sqrt());
+        }
+
+        self.colour_column(self.column);
+        self.column = (self.column + 1) % COLUMNS;
+    }
+}
+
+/// The power a full scale sine produces in its bin with this window.
+fn full_scale_power(window: &[f32]) -> f32 {
+    (window.iter().sum::<f32>() / 2.0).powi(2)
+}
+
+impl View for SpectrogramView {
+    fn element(&self) -> Option<&'static str> {
+        Some("spectrogram-view")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|spectrogram_event, _| {
+            match spectrogram_event {
+                SpectrogramViewEvent::Update => self.update(),
+                SpectrogramViewEvent::SetSampleRate(value) => self.sample_rate = *value,
+                SpectrogramViewEvent::SetFftSize(value) => self.set_fft_size(*value),
+                SpectrogramViewEvent::SetColourMap(value) => {
+                    self.colour_map = *value;
+                    self.recolour();
+                }
+                SpectrogramViewEvent::SetRange(value) => {
+                    self.range = *value;
+                    self.recolour();
+                }
+                SpectrogramViewEvent::SetShowOutput(value) => {
+                    if self.show_output != *value {
+                        self.show_output = *value;
+                        self.clear();
+                    }
+                }
+            }
+            cx.needs_redraw();
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+        let Some(image) = &self.image else {
+            return;
+        };
+
+        // The image's columns are a ring, so it's drawn in two parts with the oldest column at
+        // the left edge
+        let column_width = bounds.w / COLUMNS as f32;
+        let newest_x = bounds.x + (COLUMNS - self.column) as f32 * column_width;
+        let parts = [(self.column, COLUMNS, bounds.x), (0, self.column, newest_x)];
+
+        let paint = vg::Paint::default();
+        for (first, end, x) in parts {
+            if first == end {
+                continue;
+            }
+
+            let src = vg::Rect::from_ltrb(first as f32, 0.0, end as f32, ROWS as f32);
+            let dst =
+                vg::Rect::from_xywh(x, bounds.y, (end - first) as f32 * column_width, bounds.h);
+            canvas.draw_image_rect(
+                image,
+                Some((&src, vg::canvas::SrcRectConstraint::Strict)),
+                dst,
+                &paint,
+            );
+        }
+    }
+}
+
+pub trait SpectrogramViewExt {
+    fn fft_size<L: Lens<Target = SpectrogramFftSize>>(self, lens: L) -> Self;
+    fn colour_map<L: Lens<Target = SpectrogramColourMap>>(self, lens: L) -> Self;
+    fn range<L: Lens<Target = SpectrogramRange>>(self, lens: L) -> Self;
+    /// Show the output instead of the input. Switching clears the history.
+    fn show_output<L: Lens<Target = bool>>(self, lens: L) -> Self;
+}
+
+impl SpectrogramViewExt for Handle<'_, SpectrogramView> {
+    fn fft_size<L: Lens<Target = SpectrogramFftSize>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, value| {
+            cx.emit_to(entity, SpectrogramViewEvent::SetFftSize(value.get(cx)));
+        });
+        self
+    }
+
+    fn colour_map<L: Lens<Target = SpectrogramColourMap>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, value| {
+            cx.emit_to(entity, SpectrogramViewEvent::SetColourMap(value.get(cx)));
+        });
+        self
+    }
+
+    fn range<L: Lens<Target = SpectrogramRange>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, value| {
+            cx.emit_to(entity, SpectrogramViewEvent::SetRange(value.get(cx)));
+        });
+        self
+    }
+
+    fn show_output<L: Lens<Target = bool>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, value| {
+            cx.emit_to(entity, SpectrogramViewEvent::SetShowOutput(value.get(cx)));
+        });
+        self
+    }
+}
@@ -1,72 +1,292 @@
+use nih_plug::util;
+use realfft::num_complex::Complex32;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::sync::Arc;
 use vizia_plug::vizia::{prelude::*, vg};
 
-pub struct SpectrumView<L>
-where
-    L: Lens<Target = Vec<f32>>,
-{
-    data: L,
+use crate::dsp::analyzer::{AnalyzerTaps, SampleRing};
+use crate::{MAX_FREQUENCY, MIN_FREQUENCY};
+
+/// The number of samples each spectrum is computed from.
+const FFT_SIZE: usize = 4096;
+const NUM_BINS: usize = FFT_SIZE / 2 + 1;
+/// The level at the bottom and the top of the view.
+const MIN_DB: f32 = -96.0;
+const MAX_DB: f32 = 6.0;
+/// Grid lines are drawn at these levels.
+const GRID_DBS: [f32; 3] = [-72.0, -48.0, -24.0];
+
+/// How much of the previous spectrum is kept with every update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum SpectrumAveraging {
+    Off,
+    Fast,
+    Slow,
+}
+
+impl SpectrumAveraging {
+    pub fn label(self) -> &'static str {
+        match self {
+            SpectrumAveraging::Off => "AVG OFF",
+            SpectrumAveraging::Fast => "AVG FAST",
+            SpectrumAveraging::Slow => "AVG SLOW",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            SpectrumAveraging::Off => SpectrumAveraging::Fast,
+            SpectrumAveraging::Fast => SpectrumAveraging::Slow,
+            SpectrumAveraging::Slow => SpectrumAveraging::Off,
+        }
+    }
+
+    /// The weight of the previous spectrum's power in the next one.
+    fn weight(self) -> f32 {
+        match self {
+            SpectrumAveraging::Off => 0.0,
+            SpectrumAveraging::Fast => 0.6,
+            SpectrumAveraging::Slow => 0.9,
+        }
+    }
+}
+
+enum SpectrumViewEvent {
+    /// The audio thread wrote new samples.
+    Update,
+    SetSampleRate(f32),
+    SetAveraging(SpectrumAveraging),
+    SetPeakHold(bool),
 }
 
-impl<L> SpectrumView<L>
-where
-    L: Lens<Target = Vec<f32>>,
-{
-    pub fn new(cx: &mut Context, lens: L) -> Handle<'_, Self> {
-        Self { data: lens.clone() }.build(cx, |cx| {
-            Binding::new(cx, lens, |cx, _| {
-                cx.needs_redraw(cx.current());
+/// The averaged and held power spectrum of one signal.
+struct Trace {
+    power: Vec<f32>,
+    peak: Vec<f32>,
+}
+
+impl Trace {
+    fn new() -> Self {
+        Self {
+            power: vec![0.0; NUM_BINS],
+            peak: vec![0.0; NUM_BINS],
+        }
+    }
+}
+
+/// A Hann windowed FFT of the input and the output, drawn over a log frequency axis. The input is
+/// drawn faintly behind the output.
+pub struct SpectrumView {
+    taps: Arc<AnalyzerTaps>,
+    sample_rate: f32,
+    averaging: SpectrumAveraging,
+    peak_hold: bool,
+
+    fft: Arc<dyn RealToComplex<f32>>,
+    window: Vec<f32>,
+    /// Scratch space for the FFT.
+    samples: Vec<f32>,
+    spectrum: Vec<Complex32>,
+
+    input: Trace,
+    output: Trace,
+}
+
+impl SpectrumView {
+    pub fn new<L1, L2>(cx: &mut Context, taps: L1, sample_rate: L2) -> Handle<'_, Self>
+    where
+        L1: Lens<Target = Arc<AnalyzerTaps>>,
+        L2: Lens<Target = f32>,
+    {
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(FFT_SIZE);
+        let samples = fft.make_input_vec();
+        let spectrum = fft.make_output_vec();
+
+        Self {
+            taps: taps.get(cx),
+            sample_rate: 44100.0,
+            averaging: SpectrumAveraging::Fast,
+            peak_hold: false,
+
+            fft,
+            window: util::window::hann(FFT_SIZE),
+            samples,
+            spectrum,
+
+            input: Trace::new(),
+            output: Trace::new(),
+        }
+        .build(cx, |cx| {
+            Binding::new(cx, taps.map(|taps| taps.output.written()), |cx, _| {
+                cx.emit(SpectrumViewEvent::Update);
+            });
+            Binding::new(cx, sample_rate, |cx, value| {
+                let value = value.get(cx);
+                cx.emit(SpectrumViewEvent::SetSampleRate(value));
             });
         })
     }
+
+    /// Run the FFT on the latest samples from `ring` and fold the result into the output or the
+    /// input trace.
+    fn analyze(&mut self, ring: &SampleRing, output: bool) {
+        ring.read_latest(&mut self.samples);
+        for (sample, window) in self.samples.iter_mut().zip(&self.window) {
+            *sample *= window;
+        }
+        if self
+            .fft
+            .process(&mut self.samples, &mut self.spectrum)
+            .is_err()
+        {
+            return;
+        }
+
+        // Normalized so a full scale sine ends up at 0 dBFS
+        let window_sum: f32 = self.window.iter().sum();
+        let scale = (2.0 / window_sum).powi(2);
+        let weight = self.averaging.weight();
+        let trace = if output {
+            &mut self.output
+        } else {
+            &mut self.input
+        };
+        for ((bin, power), peak) in self
+            .spectrum
+            .iter()
+            .zip(&mut trace.power)
+            .zip(&mut trace.peak)
+        {
+            *power = *power * weight + bin.norm_sqr() * scale * (1.0 - weight);
+            *peak = if self.peak_hold {
+                peak.max(*power)
+            } else {
+                0.0
+            };
+        }
+    }
+
+    fn update(&mut self) {
+        let taps = self.taps.clone();
+        self.analyze(&taps.input, false);
+        self.analyze(&taps.output, true);
+    }
 }
 
-impl<L> View for SpectrumView<L>
-where
-    L: Lens<Target = Vec<f32>>,
-{
+impl View for SpectrumView {
     fn element(&self) -> Option<&'static str> {
         Some("spectrum-view")
     }
 
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|spectrum_event, _| {
+            match spectrum_event {
+                SpectrumViewEvent::Update => self.update(),
+                SpectrumViewEvent::SetSampleRate(value) => self.sample_rate = *value,
+                SpectrumViewEvent::SetAveraging(value) => self.averaging = *value,
+                SpectrumViewEvent::SetPeakHold(value) => self.peak_hold = *value,
+            }
+            cx.needs_redraw();
+        });
+    }
+
     fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
         let bounds = cx.bounds();
         if bounds.w == 0.0 || bounds.h == 0.0 {
             return;
         }
 
-        let data_vec = self.data.get(cx);
-        if data_vec.is_empty() {
-            return;
-        }
-
         let background_color = cx.background_color();
+        let stroke_color = cx.font_color();
+
         let mut bg_paint = vg::Paint::default();
         bg_paint.set_color(background_color);
         let rect = vg::Rect::from_xywh(bounds.x, bounds.y, bounds.w, bounds.h);
         canvas.draw_rect(&rect, &bg_paint);
 
-        let stroke_color = cx.font_color();
+        let db_to_y = |db: f32| {
+            let position = (db - MIN_DB) / (MAX_DB - MIN_DB);
+            bounds.y + bounds.h - position.clamp(0.0, 1.0) * bounds.h
+        };
 
-        let max_val = data_vec.iter().copied().fold(0.0f32, f32::max);
+        let mut grid_path = vg::Path::new();
+        for db in GRID_DBS {
+            let y = db_to_y(db);
+            grid_path.move_to((bounds.x, y));
+            grid_path.line_to((bounds.x + bounds.w, y));
+        }
+        let mut grid_paint = vg::Paint::default();
+        grid_paint.set_color(stroke_color);
+        grid_paint.set_alpha_f(0.15);
+        grid_paint.set_stroke_width(1.0);
+        grid_paint.set_style(vg::PaintStyle::Stroke);
+        canvas.draw_path(&grid_path, &grid_paint);
+
+        let spectrum_path = |powers: &[f32]| {
+            let log_range = (MAX_FREQUENCY / MIN_FREQUENCY).ln();
+            let bin_width = self.sample_rate / FFT_SIZE as f32;
+            let mut path = vg::Path::new();
+            let mut started = false;
+            for (bin, power) in powers.iter().enumerate().skip(1) {
+                let frequency = bin as f32 * bin_width;
+                if !(MIN_FREQUENCY..=MAX_FREQUENCY).contains(&frequency) {
+                    continue;
+                }
 
-        let num_bins = data_vec.len();
-        let bin_width = bounds.w / num_bins as f32;
+                let x = bounds.x + (frequency / MIN_FREQUENCY).ln() / log_range * bounds.w;
+                let y = db_to_y(util::gain_to_db(power.sqrt()));
+                if started {
+                    path.line_to((x, y));
+                } else {
+                    path.move_to((x, y));
+                    started = true;
+                }
+            }
 
-        let mut spectrum_path = vg::Path::new();
+            path
+        };
 
-        for (i, &val) in data_vec.iter().enumerate() {
-            let normalized_val = if max_val > 0.0 { val / max_val } else { 0.0 };
-            let bar_height = (normalized_val * bounds.h).clamp(0.0, bounds.h);
-            let x = bounds.x + i as f32 * bin_width;
-            let y = bounds.y + bounds.h - bar_height;
+        let mut paint = vg::Paint::default();
+        paint.set_color(stroke_color);
+        paint.set_stroke_width(1.0);
+        paint.set_style(vg::PaintStyle::Stroke);
+        paint.set_anti_alias(true);
 
-            spectrum_path.add_rect(vg::Rect::new(x, y, bin_width, bar_height), None);
+        if self.peak_hold {
+            paint.set_alpha_f(0.25);
+            canvas.draw_path(&spectrum_path(&self.output.peak), &paint);
         }
 
-        let mut fill_paint = vg::Paint::default();
-        fill_paint.set_color(stroke_color);
-        fill_paint.set_style(vg::PaintStyle::Fill);
-        fill_paint.set_anti_alias(true);
-        canvas.draw_path(&spectrum_path, &fill_paint);
+        paint.set_alpha_f(0.35);
+        canvas.draw_path(&spectrum_path(&self.input.power), &paint);
+
+        paint.set_alpha_f(1.0);
+        paint.set_stroke_width(cx.border_width().max(1.5));
+        canvas.draw_path(&spectrum_path(&self.output.power), &paint);
+    }
+}
+
+pub trait SpectrumViewExt {
+    fn averaging<L: Lens<Target = SpectrumAveraging>>(self, lens: L) -> Self;
+    /// Keep the highest level each bin of the output has reached. Switching it off clears the
+    /// held levels.
+    fn peak_hold<L: Lens<Target = bool>>(self, lens: L) -> Self;
+}
+
+impl SpectrumViewExt for Handle<'_, SpectrumView> {
+    fn averaging<L: Lens<Target = SpectrumAveraging>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, value| {
+            cx.emit_to(entity, SpectrumViewEvent::SetAveraging(value.get(cx)));
+        });
+        self
+    }
+
+    fn peak_hold<L: Lens<Target = bool>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, value| {
+            cx.emit_to(entity, SpectrumViewEvent::SetPeakHold(value.get(cx)));
+        });
+        self
     }
 }
@@ -0,0 +1,115 @@
+use vizia_plug::vizia::{prelude::*, vg};
+
+use crate::dsp::zap::sweep_frequency;
+use crate::{MAX_FREQUENCY, MIN_FREQUENCY};
+
+/// The number of line segments used to draw the sweep.
+const CURVE_RESOLUTION: usize = 64;
+
+pub enum SweepViewEvent {
+    SetStartFrequency(f32),
+    SetEndFrequency(f32),
+    SetCurve(f32),
+}
+
+/// Draws the zap sweep's frequency over time, with the frequency on a log scale.
+pub struct SweepView {
+    start_frequency: f32,
+    end_frequency: f32,
+    curve: f32,
+}
+
+impl SweepView {
+    pub fn new<L1, L2, L3>(
+        cx: &mut Context,
+        start_frequency: L1,
+        end_frequency: L2,
+        curve: L3,
+    ) -> Handle<'_, Self>
+    where
+        L1: Lens<Target = f32>,
+        L2: Lens<Target = f32>,
+        L3: Lens<Target = f32>,
+    {
+        Self {
+            start_frequency: MAX_FREQUENCY,
+            end_frequency: MIN_FREQUENCY,
+            curve: 0.0,
+        }
+        .build(cx, |cx| {
+            Binding::new(cx, start_frequency, |cx, value| {
+                let value = value.get(cx);
+                cx.emit(SweepViewEvent::SetStartFrequency(value));
+            });
+            Binding::new(cx, end_frequency, |cx, value| {
+                let value = value.get(cx);
+                cx.emit(SweepViewEvent::SetEndFrequency(value));
+            });
+            Binding::new(cx, curve, |cx, value| {
+                let value = value.get(cx);
+                cx.emit(SweepViewEvent::SetCurve(value));
+            });
+        })
+    }
+}
+
+impl View for SweepView {
+    fn element(&self) -> Option<&'static str> {
+        Some("sweep-view")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|sweep_event, _| {
+            match sweep_event {
+                SweepViewEvent::SetStartFrequency(value) => self.start_frequency = *value,
+                SweepViewEvent::SetEndFrequency(value) => self.end_frequency = *value,
+                SweepViewEvent::SetCurve(value) => self.curve = *value,
+            }
+            cx.needs_redraw();
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let background_color = cx.background_color();
+        let stroke_color = cx.font_color();
+
+        let mut bg_paint = vg::Paint::default();
+        bg_paint.set_color(background_color);
+        let rect = vg::Rect::from_xywh(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.draw_rect(&rect, &bg_paint);
+
+        let log_range = (MAX_FREQUENCY / MIN_FREQUENCY).ln();
+        let frequency_to_y = |frequency: f32| {
+            let position = (frequency / MIN_FREQUENCY).ln() / log_range;
+            bounds.y + bounds.h - position.clamp(0.0, 1.0) * bounds.h
+        };
+
+        let mut curve_path = vg::Path::new();
+        for i in 0..=CURVE_RESOLUTION {
+            let t = i as f32 / CURVE_RESOLUTION as f32;
+            let frequency =
+                sweep_frequency(self.start_frequency, self.end_frequency, self.curve, t);
+            let x = bounds.x + t * bounds.w;
+            let y = frequency_to_y(frequency);
+            if i == 0 {
+                curve_path.move_to((x, y));
+            } else {
+                curve_path.line_to((x, y));
+            }
+        }
+
+        let mut stroke_paint = vg::Paint::default();
+        stroke_paint.set_color(stroke_color);
+        stroke_paint.set_stroke_width(cx.border_width().max(1.5));
+        stroke_paint.set_style(vg::PaintStyle::Stroke);
+        stroke_paint.set_stroke_cap(vg::PaintCap::Round);
+        stroke_paint.set_stroke_join(vg::PaintJoin::Round);
+        stroke_paint.set_anti_alias(true);
+        canvas.draw_path(&curve_path, &stroke_paint);
+    }
+}
@@ -1,57 +1,107 @@
-use std::collections::VecDeque;
+use std::sync::Arc;
 use vizia_plug::vizia::{prelude::*, vg};
 
+use crate::dsp::analyzer::{AnalyzerTaps, RING_CAPACITY};
+
+/// The time bases the scope can be zoomed to, in milliseconds across the whole view.
+pub const TIME_BASES_MS: [f32; 7] = [2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0];
+/// The number of samples copied out of the rings. The trigger is searched for in whatever's left
+/// after the visible window, so this needs to be comfortably longer than the longest time base.
+const HISTORY: usize = RING_CAPACITY / 2;
+/// The dry signal needs to rise through zero from below this level to count as a trigger, so
+/// noise around zero doesn't make the trace jitter.
+const TRIGGER_HYSTERESIS: f32 = 0.01;
+
 pub enum WaveformViewEvent {
-    AddSample1(f32),
-    AddSample2(f32),
+    /// The audio thread wrote new samples.
+    Update,
+    SetSampleRate(f32),
+    SetTimeBase(f32),
+    SetFrozen(bool),
+    SetOverlay(bool),
 }
 
+/// A triggered oscilloscope of the wet signal. The trigger is the dry signal's last rising zero
+/// crossing, so the chirp dispersion smears out of a transient lines up with the transient itself.
+/// The dry signal can be drawn faintly behind the wet one.
 pub struct WaveformView {
-    buffer1: VecDeque<f32>,
-    buffer2: VecDeque<f32>,
-    max_samples: usize,
+    taps: Arc<AnalyzerTaps>,
+    sample_rate: f32,
+    time_base_ms: f32,
+    frozen: bool,
+    overlay: bool,
+
+    dry: Vec<f32>,
+    wet: Vec<f32>,
+    /// The index in `dry` and `wet` the visible window starts at.
+    window_start: usize,
 }
 
 impl WaveformView {
-    pub fn new<L1, L2>(
-        cx: &mut Context,
-        lens1: L1,
-        lens2: L2,
-        max_samples: usize,
-    ) -> Handle<'_, Self>
+    pub fn new<L1, L2>(cx: &mut Context, taps: L1, sample_rate: L2) -> Handle<'_, Self>
     where
-        L1: Lens<Target = f32>,
+        L1: Lens<Target = Arc<AnalyzerTaps>>,
         L2: Lens<Target = f32>,
     {
         Self {
-            buffer1: VecDeque::from(vec![0.0; max_samples]),
-            buffer2: VecDeque::from(vec![0.0; max_samples]),
-            max_samples,
+            taps: taps.get(cx),
+            sample_rate: 44100.0,
+            time_base_ms: 20.0,
+            frozen: false,
+            overlay: true,
+
+            dry: vec![0.0; HISTORY],
+            wet: vec![0.0; HISTORY],
+            window_start: 0,
         }
         .build(cx, |cx| {
-            Binding::new(cx, lens1, |cx, sample_lens1| {
-                let sample = sample_lens1.get(cx);
-                cx.emit(WaveformViewEvent::AddSample1(sample));
+            Binding::new(cx, taps.map(|taps| taps.wet.written()), |cx, _| {
+                cx.emit(WaveformViewEvent::Update);
             });
-            Binding::new(cx, lens2, |cx, sample_lens2| {
-                let sample = sample_lens2.get(cx);
-                cx.emit(WaveformViewEvent::AddSample2(sample));
+            Binding::new(cx, sample_rate, |cx, value| {
+                let value = value.get(cx);
+                cx.emit(WaveformViewEvent::SetSampleRate(value));
             });
         })
     }
 
-    fn push_sample1(&mut self, sample: f32) {
-        if self.buffer1.len() >= self.max_samples {
-            self.buffer1.pop_front();
+    fn window_len(&self) -> usize {
+        ((self.time_base_ms * self.sample_rate / 1000.0) as usize).clamp(2, HISTORY / 2)
+    }
+
+    fn update(&mut self) {
+        if self.frozen {
+            return;
         }
-        self.buffer1.push_back(sample);
+
+        // The wet ring is written last, so reading it first keeps the two traces lined up as
+        // closely as possible while the audio thread keeps writing
+        self.taps.wet.read_latest(&mut self.wet);
+        self.taps.input.read_latest(&mut self.dry);
+        self.find_trigger();
     }
 
-    fn push_sample2(&mut self, sample: f32) {
-        if self.buffer2.len() >= self.max_samples {
-            self.buffer2.pop_front();
+    /// Place the visible window so it starts at the latest trigger that still leaves a full window
+    /// after it. Without one the scope runs free and shows the latest samples.
+    fn find_trigger(&mut self) {
+        let window_len = self.window_len();
+        let latest_start = HISTORY - window_len;
+
+        // The signal needs to dip far enough below zero before a rising crossing counts
+        let mut armed = false;
+        let mut trigger = None;
+        for idx in 1..=latest_start {
+            let sample = self.dry[idx];
+            if armed && sample > 0.0 && self.dry[idx - 1] <= 0.0 {
+                trigger = Some(idx);
+                armed = false;
+            }
+            if sample < -TRIGGER_HYSTERESIS {
+                armed = true;
+            }
         }
-        self.buffer2.push_back(sample);
+
+        self.window_start = trigger.unwrap_or(latest_start);
     }
 }
 
@@ -61,15 +111,18 @@ impl View for WaveformView {
     }
 
     fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
-        event.map(|waveform_event, _| match waveform_event {
-            WaveformViewEvent::AddSample1(sample1) => {
-                self.push_sample1(*sample1);
-                cx.needs_redraw();
-            }
-            WaveformViewEvent::AddSample2(sample2) => {
-                self.push_sample2(*sample2);
-                cx.needs_redraw();
+        event.map(|waveform_event, _| {
+            match waveform_event {
+                WaveformViewEvent::Update => self.update(),
+                WaveformViewEvent::SetSampleRate(value) => self.sample_rate = *value,
+                WaveformViewEvent::SetTimeBase(value) => {
+                    self.time_base_ms = *value;
+                    self.find_trigger();
+                }
+                WaveformViewEvent::SetFrozen(value) => self.frozen = *value,
+                WaveformViewEvent::SetOverlay(value) => self.overlay = *value,
             }
+            cx.needs_redraw();
         });
     }
 
@@ -90,90 +143,27 @@ impl View for WaveformView {
 
         let mid_y = bounds.y + bounds.h / 2.0;
         let half_h = bounds.h / 2.0;
-        let x_step = bounds.w / (self.max_samples as f32 - 1.0);
-
-        let mut wave_path = vg::Path::new();
-
-        //// buffer1
-        // Top half
-        let mut first = true;
-        for (i, &sample) in self.buffer1.iter().enumerate() {
-            let x = bounds.x + i as f32 * x_step;
-            let sample_height = sample.abs().clamp(0.0, 1.0) * half_h;
-            let y = mid_y - sample_height;
-            if first {
-                wave_path.move_to((x, y));
-                first = false;
-            } else {
-                wave_path.line_to((x, y));
-            }
-        }
+        let window_len = self.window_len();
+        let window = self.window_start..(self.window_start + window_len).min(HISTORY);
 
-        // Bottom half, in reverse
-        for (i, &sample) in self.buffer1.iter().enumerate().rev() {
-            let x = bounds.x + i as f32 * x_step;
-            let sample_height = sample.abs().clamp(0.0, 1.0) * half_h;
-            let y = mid_y + sample_height;
-            wave_path.line_to((x, y));
-        }
-
-        wave_path.close();
-
-        // 渲染填充区域
-        let mut fill_paint = vg::Paint::default();
-        let fill_color = stroke_color;
-        fill_paint.set_dither(true);
-        fill_paint.set_color(fill_color);
-        fill_paint.set_alpha(114);
-        fill_paint.set_style(vg::PaintStyle::Fill);
-        fill_paint.set_anti_alias(true);
-        canvas.draw_path(&wave_path, &fill_paint);
-
-        // 渲染轮廓线
         let mut stroke_paint = vg::Paint::default();
         stroke_paint.set_color(stroke_color);
-        stroke_paint.set_stroke_width(stroke_width);
         stroke_paint.set_style(vg::PaintStyle::Stroke);
         stroke_paint.set_stroke_cap(vg::PaintCap::Round);
         stroke_paint.set_stroke_join(vg::PaintJoin::Round);
         stroke_paint.set_anti_alias(true);
-        canvas.draw_path(&wave_path, &stroke_paint);
-
-        //// buffer2
-        // Top half
-        let mut first = true;
-        for (i, &sample) in self.buffer2.iter().enumerate() {
-            let x = bounds.x + i as f32 * x_step;
-            let sample_height = sample.abs().clamp(0.0, 1.0) * half_h;
-            let y = mid_y - sample_height;
-            if first {
-                wave_path.move_to((x, y));
-                first = false;
-            } else {
-                wave_path.line_to((x, y));
-            }
-        }
 
-        // Bottom half, in reverse
-        for (i, &sample) in self.buffer2.iter().enumerate().rev() {
-            let x = bounds.x + i as f32 * x_step;
-            let sample_height = sample.abs().clamp(0.0, 1.0) * half_h;
-            let y = mid_y + sample_height;
-            wave_path.line_to((x, y));
+        if self.overlay {
+            stroke_paint.set_alpha_f(0.35);
+            stroke_paint.set_stroke_width(1.0);
+            let dry_path = trace_path(&self.dry[window.clone()], bounds, mid_y, half_h);
+            canvas.draw_path(&dry_path, &stroke_paint);
         }
 
-        wave_path.close();
-
-        // 渲染轮廓线
-        let mut stroke_paint = vg::Paint::default();
-        stroke_paint.set_color(Color::palegreen());
-        stroke_paint.set_alpha_f(0.5);
+        stroke_paint.set_alpha_f(1.0);
         stroke_paint.set_stroke_width(stroke_width);
-        stroke_paint.set_style(vg::PaintStyle::Stroke);
-        stroke_paint.set_stroke_cap(vg::PaintCap::Round);
-        stroke_paint.set_stroke_join(vg::PaintJoin::Round);
-        stroke_paint.set_anti_alias(true);
-        canvas.draw_path(&wave_path, &stroke_paint);
+        let wet_path = trace_path(&self.wet[window], bounds, mid_y, half_h);
+        canvas.draw_path(&wet_path, &stroke_paint);
 
         // 绘制零位基准线
         let mut center_line = vg::Path::new();
@@ -186,3 +176,80 @@ impl View for WaveformView {
         canvas.draw_path(&center_line, &center_paint);
     }
 }
+
+/// Draw `samples` across the view. With more samples than pixels every pixel column gets a line
+/// from its lowest to its highest sample, so short peaks don't get lost to the decimation.
+fn trace_path(samples: &[f32], bounds: BoundingBox, mid_y: f32, half_h: f32) -> vg::Path {
+    let sample_to_y = |sample: f32| mid_y - sample.clamp(-1.0, 1.0) * half_h;
+    let mut path = vg::Path::new();
+    if samples.len() < 2 {
+        return path;
+    }
+
+    let columns = bounds.w.ceil() as usize;
+    if samples.len() <= columns {
+        let x_step = bounds.w / (samples.len() - 1) as f32;
+        for (i, &sample) in samples.iter().enumerate() {
+            let point = (bounds.x + i as f32 * x_step, sample_to_y(sample));
+            if i == 0 {
+                path.move_to(point);
+            } else {
+                path.line_to(point);
+            }
+        }
+    } else {
+        for column in 0..columns {
+            let start = column * samples.len() / columns;
+            let end = ((column + 1) * samples.len() / columns).max(start + 1);
+            let (min, max) = samples[start..end]
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(min, max), &sample| {
+                    (min.min(sample), max.max(sample))
+                });
+
+            let x = bounds.x + column as f32;
+            if column == 0 {
+                path.move_to((x, sample_to_y(max)));
+            } else {
+                path.line_to((x, sample_to_y(max)));
+            }
+            path.line_to((x, sample_to_y(min)));
+        }
+    }
+
+    path
+}
+
+pub trait WaveformViewExt {
+    fn time_base<L: Lens<Target = f32>>(self, lens: L) -> Self;
+    /// Stop taking in new samples, so the current trace can be looked at.
+    fn frozen<L: Lens<Target = bool>>(self, lens: L) -> Self;
+    /// Draw the dry signal behind the wet one.
+    fn overlay<L: Lens<Target = bool>>(self, lens: L) -> Self;
+}
+
+impl WaveformViewExt for Handle<'_, WaveformView> {
+    fn time_base<L: Lens<Target = f32>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, value| {
+            cx.emit_to(entity, WaveformViewEvent::SetTimeBase(value.get(cx)));
+        });
+        self
+    }
+
+    fn frozen<L: Lens<Target = bool>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, value| {
+            cx.emit_to(entity, WaveformViewEvent::SetFrozen(value.get(cx)));
+        });
+        self
+    }
+
+    fn overlay<L: Lens<Target = bool>>(mut self, lens: L) -> Self {
+        let entity = self.entity();
+        Binding::new(self.context(), lens, move |cx, value| {
+            cx.emit_to(entity, WaveformViewEvent::SetOverlay(value.get(cx)));
+        });
+        self
+    }
+}